@@ -1,2 +1,2 @@
-mod file_management;
-mod file_log_management;
+pub mod file_management;
+pub mod file_log_management;
@@ -20,30 +20,39 @@
 //
 // // Additional logging functions
 
+use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs::{self}; //, File
-use std::io::{self, Read, Write};
+use std::io;
 use std::path::{Path, PathBuf};
-use sha2::{Sha256, Digest};
+use chrono::{DateTime, Local};
 
 
 use std::collections::HashMap;
 
-// #[derive(Serialize, Deserialize, Debug)]
-pub struct ObjectId(String); // Assuming ObjectId is a wrapper around a String
+// FileLogManager reads repositories written in the legacy `.rev` format (.rev/store, .rev/branches, .rev/branch).
+// New data is only written in the `.mdv` layout, see `repository::layout::upgrade_legacy`.
 
-// #[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ObjectId(pub String); // Assuming ObjectId is a wrapper around a String
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Directory {
     files: HashMap<String, ObjectId>,
 }
 
+impl Directory {
+    pub fn files(&self) -> &HashMap<String, ObjectId> {
+        &self.files
+    }
+}
+
 
-// Assuming SnapShot and other relevant structs are defined here or imported
-// #[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct SnapShot {
-    message: String,
-    directory: ObjectId,
-    previous: Option<ObjectId>,
+    pub message: String,
+    pub directory: ObjectId,
+    pub previous: Option<ObjectId>,
 }
 
 pub struct FileLogManager {
@@ -58,6 +67,7 @@ impl FileLogManager {
         let branches_dir = repo_path.join(".rev/branches");
         let current_branch_path = repo_path.join(".rev/branch");
         let current_branch = fs::read_to_string(current_branch_path)
+            .map(|branch| branch.trim().to_string())
             .unwrap_or_else(|_| "main".to_string()); // Default to 'main' branch
 
         FileLogManager {
@@ -67,66 +77,67 @@ impl FileLogManager {
         }
     }
 
-    pub fn log_snapshot(&self, snapshot: &SnapShot) -> io::Result<()> {
-        let snapshot_json = serde_json::to_string_pretty(snapshot)?;
-        let snapshot_id = self.generate_object_id(&snapshot_json); // Implement this method based on your hash function
-        let snapshot_path = self.object_store_path.join(&snapshot_id);
-
-        fs::write(snapshot_path, snapshot_json)?;
-
-        let branch_file_path = self.branches_dir.join(&self.current_branch);
-        fs::write(branch_file_path, snapshot_id)?;
+    pub fn current_branch(&self) -> &str {
+        &self.current_branch
+    }
 
-        Ok(())
+    pub fn list_branches(&self) -> io::Result<Vec<String>> {
+        if !self.branches_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut branches = Vec::new();
+        for entry in fs::read_dir(&self.branches_dir)? {
+            branches.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        branches.sort();
+        Ok(branches)
     }
 
+    #[allow(dead_code)]
     pub fn get_latest_snapshot(&self) -> io::Result<SnapShot> {
         let branch_file_path = self.branches_dir.join(&self.current_branch);
         let snapshot_id = fs::read_to_string(branch_file_path)?;
 
-        let snapshot_path = self.object_store_path.join(snapshot_id);
+        self.get_snapshot_by_id(&ObjectId(snapshot_id.trim().to_string()))
+    }
+
+    pub fn get_snapshot_by_id(&self, snapshot_id: &ObjectId) -> io::Result<SnapShot> {
+        let snapshot_path = self.object_store_path.join(&snapshot_id.0);
         let snapshot_json = fs::read_to_string(snapshot_path)?;
         let snapshot: SnapShot = serde_json::from_str(&snapshot_json)?;
 
         Ok(snapshot)
     }
 
-    pub fn generate_object_id(&self, content: &str) -> ObjectId {
-        let mut hasher = Sha256::new();
-        hasher.update(content);
-        let result = hasher.finalize();
-        ObjectId(format!("{:x}", result))
-        // ObjectId(hex::encode(result))
+    // the store does not record dates, the time the snapshot was written is the closest we have
+    pub fn get_snapshot_date(&self, snapshot_id: &str) -> io::Result<String> {
+        let modified = fs::metadata(self.object_store_path.join(snapshot_id))?.modified()?;
+        Ok(DateTime::<Local>::from(modified).to_rfc3339())
+    }
 
+    pub fn get_directory(&self, directory_id: &str) -> io::Result<Directory> {
+        let directory_json = fs::read_to_string(self.object_store_path.join(directory_id))?;
+        let directory: Directory = serde_json::from_str(&directory_json)?;
+
+        Ok(directory)
     }
-    // pub fn revert_to_snapshot(&self, snapshot_id: ObjectId) -> io::Result<()> {
-    //     let snapshot = self.get_snapshot_by_id(&snapshot_id)?;
-    //     // Logic to revert the repository to the state described by `snapshot`
-    //     // This might involve checking out files, resetting directory states, etc.
-    //
-    //     Ok(())
-    // }
-
-    fn get_snapshot_by_id(&self, snapshot_id: &ObjectId) -> io::Result<SnapShot> {
-        let snapshot_path = self.object_store_path.join(&snapshot_id.0);
-        let snapshot_json = fs::read_to_string(snapshot_path)?;
-        let snapshot: SnapShot = serde_json::from_str(&snapshot_json)?;
 
-        Ok(snapshot)
+    pub fn get_object(&self, object_id: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.object_store_path.join(object_id))
     }
 
-    // pub fn get_snapshot_history(&self) -> io::Result<Vec<SnapShot>> {
-        // let mut history = Vec::new();
-        // let mut current_snapshot = self.get_latest_snapshot()?;
+    // get_snapshot_history: (id, snapshot) pairs of a branch, newest first
+    pub fn get_snapshot_history(&self, branch: &str) -> io::Result<Vec<(String, SnapShot)>> {
+        let mut history = Vec::new();
+        let tip = fs::read_to_string(self.branches_dir.join(branch))?;
+        let mut current_id = Some(tip.trim().to_string());
 
-        // while let Some(prev_id) = current_snapshot.previous {
-        //     history.push(current_snapshot); // Used moved value
-        //     current_snapshot = self.get_snapshot_by_id(&prev_id)?;
-        // }
+        while let Some(id) = current_id {
+            let snapshot = self.get_snapshot_by_id(&ObjectId(id.clone()))?;
+            current_id = snapshot.previous.as_ref().map(|prev_id| prev_id.0.clone());
+            history.push((id, snapshot));
+        }
 
-        // history.push(current_snapshot); // Add the initial snapshot
-        // Ok(history)
-        // to_do!("Implement this method")
-    // }
-    // Additional methods like revert_to_snapshot, get_snapshot_history, etc.
+        Ok(history)
+    }
 }
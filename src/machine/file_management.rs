@@ -7,17 +7,18 @@ pub fn create_file(path: &str) -> Result<(), std::io::Error> {
     file.write_all(b"")?;
     Ok(())
 }
-pub fn read_file(path: &str) -> Result<String, std::io::Error> {
+// files are read and written as raw bytes, their content does not have to be text
+pub fn read_file(path: &str) -> Result<Vec<u8>, std::io::Error> {
     let mut file = File::open(path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
     // file.close()?; // This is not needed in Rust, but it's a good habit to close files
     Ok(contents)
 }
 
-pub fn write_file(path: &str, content: &str) -> Result<(), std::io::Error> {
+pub fn write_file(path: &str, content: &[u8]) -> Result<(), std::io::Error> {
     let mut file = File::create(path)?;
-    file.write_all(content.as_bytes())?;
+    file.write_all(content)?;
     Ok(())
 }
 
@@ -32,6 +33,24 @@ pub fn list_files_in_dir(path: &str) -> Result<Vec<String>, std::io::Error> {
     Ok(files)
 }
 
+// list every file below a directory, folders named in skip_dirs are not entered
+pub fn list_files_recursive(path: &str, skip_dirs: &[&str]) -> Result<Vec<String>, std::io::Error> {
+    let mut files = Vec::new();
+    for entry_path in list_files_in_dir(path)? {
+        let entry = Path::new(&entry_path);
+        let name = entry.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if entry.is_dir() {
+            if !skip_dirs.contains(&name) {
+                files.extend(list_files_recursive(&entry_path, skip_dirs)?);
+            }
+        } else {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
 // pub fn list_files_in_directory(directory_path: &str) -> io::Result<Vec<String>> {
 //     let paths = fs::read_dir(directory_path)?
 //         .map(|entry| entry.map(|e| e.path().display().to_string()))
@@ -40,6 +59,7 @@ pub fn list_files_in_dir(path: &str) -> Result<Vec<String>, std::io::Error> {
 // }
 
 
+#[allow(dead_code)]
 pub fn file_exists(path: &str) -> bool {
     Path::new(path).exists()
 }
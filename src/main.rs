@@ -1,14 +1,86 @@
 mod machine;
 mod repository;
 mod user;
-mod test;
 
-fn function() {
-    println!("called `function()`");
-}
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use user::interaction::interaction::{InteractionManager, SendError};
+use user::user_interaction::{CommandInterpreter, ExecutableCommand, InterpretationError};
+
+// mdv <command> [<arguments>...] runs one command and exits with its status:
+//
+// 0    the command succeeded
+// 1    the command failed in the repository
+// 2    the command line is invalid or the command is not supported
+//
+// mdv shell starts the interactive prompt, mdv help (or no command) lists the commands.
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
 fn main() {
-    test::function();
-    function();
-    test::indirect_access();
-    test::nested::function();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(|command| command.as_str()) {
+        None | Some("help") | Some("--help") | Some("-h") => {
+            CommandInterpreter::guide_user_input();
+            if args.is_empty() { EXIT_USAGE } else { 0 }
+        },
+        Some("shell") => shell(),
+        Some(_) => {
+            let parts: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+            run(CommandInterpreter::interpret_arguments(&parts))
+        },
+    };
+    process::exit(code);
+}
+
+// run: execute an interpreted command, print its result and return the exit status
+fn run(command: Result<ExecutableCommand, InterpretationError>) -> i32 {
+    let command = match command {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_USAGE;
+        },
+    };
+    match InteractionManager::send_command_to_repository(command) {
+        Ok(message) => {
+            // cat already wrote the file to stdout and returns nothing. A reader that stops early
+            // (mdv log | head) closes the pipe, the command still succeeded.
+            if !message.is_empty() {
+                if let Err(err) = writeln!(io::stdout().lock(), "{}", message) {
+                    if err.kind() != io::ErrorKind::BrokenPipe {
+                        eprintln!("{}", err);
+                        return EXIT_FAILURE;
+                    }
+                }
+            }
+            0
+        },
+        Err(SendError::RepositoryError(message)) => {
+            eprintln!("{}", message);
+            EXIT_FAILURE
+        },
+        Err(SendError::UnsupportedCommand) => {
+            eprintln!("This command is not supported yet.");
+            EXIT_USAGE
+        },
+    }
+}
+
+// shell: read commands until `exit` or the end of the input, with the status of the last one
+fn shell() -> i32 {
+    CommandInterpreter::guide_user_input();
+    let mut code = 0;
+    while let Some(input) = CommandInterpreter::get_user_input() {
+        match input.as_str() {
+            "" => {},
+            "exit" => return code,
+            _ => code = run(CommandInterpreter::interpret_command(input)),
+        }
+    }
+    // the end of the input leaves the prompt on its line
+    let _ = writeln!(io::stdout().lock());
+    code
 }
@@ -0,0 +1,19 @@
+pub mod config;
+pub mod diff;
+pub mod graph;
+pub mod ignore;
+pub mod layout;
+pub mod log;
+pub mod merge;
+pub mod objects;
+pub mod policy;
+pub mod refs;
+#[allow(clippy::module_inception)]
+pub mod repository;
+pub mod revision;
+pub mod revspec;
+pub mod staging;
+pub mod stash;
+pub mod status;
+pub mod tag;
+pub mod tree;
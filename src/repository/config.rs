@@ -0,0 +1,379 @@
+use crate::repository::layout;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Settings are INI files:
+//
+// # comment, ; comment
+// [user]                 a section, names are case-insensitive
+//     name = Jane Doe    keys are read as section.key, here user.name
+// [remote "origin"]      a subsection, its keys are read as remote.origin.key
+//
+// Layers, each one overriding the ones before it:
+//
+// system        /etc/mdvconfig, or the file named by MDV_CONFIG_SYSTEM
+// user          ~/.mdvconfig, or the file named by MDV_CONFIG_GLOBAL
+// repository    .mdv/config
+// environment   MDV_CONFIG_COUNT=<n> with MDV_CONFIG_KEY_<i> and MDV_CONFIG_VALUE_<i> for i below n
+//
+// Settings in use: user.name, user.email, init.defaultbranch, diff.context, and file.* for the file name
+// policy (see policy.rs).
+
+pub const USER_CONFIG_FILE: &str = ".mdvconfig";
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/mdvconfig";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigLayer {
+    System,
+    User,
+    Repository,
+    Environment,
+}
+
+impl ConfigLayer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigLayer::System => "system",
+            ConfigLayer::User => "user",
+            ConfigLayer::Repository => "repository",
+            ConfigLayer::Environment => "environment",
+        }
+    }
+
+    // file_path: the file of a layer, None for the environment or when there is no home folder
+    pub fn file_path(&self, path: Option<&str>) -> Option<String> {
+        match self {
+            ConfigLayer::System => Some(env::var("MDV_CONFIG_SYSTEM").unwrap_or_else(|_| SYSTEM_CONFIG_PATH.to_string())),
+            ConfigLayer::User => env::var("MDV_CONFIG_GLOBAL").ok().or_else(user_config_path),
+            ConfigLayer::Repository => path.map(layout::config_path),
+            ConfigLayer::Environment => None,
+        }
+    }
+}
+
+// a setting with the layer and the file or variable it comes from
+#[derive(Debug, Clone)]
+pub struct ConfigValue {
+    pub key: String,
+    pub value: String,
+    pub layer: ConfigLayer,
+    pub origin: String,
+}
+
+// parse_ini: the values of an INI text by their full key, later lines win
+pub fn parse_ini(text: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    let mut section = String::new();
+    for line in text.lines() {
+        if let Some(header) = section_header(line) {
+            section = header;
+            continue;
+        }
+        if let Some((key, value)) = key_value(line) {
+            values.insert(format!("{}.{}", section, key), value);
+        }
+    }
+    values
+}
+
+// section_header: the section name of a `[section]` or `[section "sub"]` line
+fn section_header(line: &str) -> Option<String> {
+    let header = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    Some(match header.split_once(char::is_whitespace) {
+        Some((name, sub)) => format!("{}.{}", name.to_lowercase(), sub.trim().trim_matches('"')),
+        None => header.trim().to_lowercase(),
+    })
+}
+
+// key_value: the lowercase key and the value of a `key = value` line, a key alone is true
+fn key_value(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with('[') {
+        return None;
+    }
+    let (key, value) = line.split_once('=').unwrap_or((line, "true"));
+    let value = value.trim();
+    let value = value.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')).unwrap_or(value);
+    Some((key.trim().to_lowercase(), value.to_string()))
+}
+
+// split_key: section (with its subsection) and name of a key, None when it is not section.name
+fn split_key(key: &str) -> Option<(String, String)> {
+    let (section, name) = key.rsplit_once('.')?;
+    let valid = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let section_name = section.split('.').next().unwrap_or("");
+    if !valid(section_name) || !valid(name) {
+        return None;
+    }
+    let section = match section.split_once('.') {
+        Some((name, sub)) => format!("{}.{}", name.to_lowercase(), sub),
+        None => section.to_lowercase(),
+    };
+    Some((section, name.to_lowercase()))
+}
+
+// user_config_path: ~/.mdvconfig, None when there is no home folder
+pub fn user_config_path() -> Option<String> {
+    env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()
+        .map(|home| format!("{}/{}", home, USER_CONFIG_FILE))
+}
+
+// load: every setting of every layer, the ones that win last. Without a repository path only the
+// system, user and environment layers are read.
+pub fn load(path: Option<&str>) -> Vec<ConfigValue> {
+    let mut values = Vec::new();
+    for layer in [ConfigLayer::System, ConfigLayer::User, ConfigLayer::Repository] {
+        let Some(file_path) = layer.file_path(path) else { continue };
+        let Ok(text) = fs::read_to_string(&file_path) else { continue };
+        values.extend(parse_ini(&text).into_iter()
+            .map(|(key, value)| ConfigValue { key, value, layer, origin: file_path.clone() }));
+    }
+
+    let count: usize = env::var("MDV_CONFIG_COUNT").ok().and_then(|count| count.parse().ok()).unwrap_or(0);
+    for index in 0..count {
+        let key = env::var(format!("MDV_CONFIG_KEY_{}", index)).ok().and_then(|key| split_key(&key));
+        if let (Some((section, name)), Ok(value)) = (key, env::var(format!("MDV_CONFIG_VALUE_{}", index))) {
+            values.push(ConfigValue {
+                key: format!("{}.{}", section, name),
+                value,
+                layer: ConfigLayer::Environment,
+                origin: format!("MDV_CONFIG_KEY_{}", index),
+            });
+        }
+    }
+    values
+}
+
+// lookup: the winning setting of a key
+pub fn lookup(path: Option<&str>, key: &str) -> Option<ConfigValue> {
+    let (section, name) = split_key(key)?;
+    let key = format!("{}.{}", section, name);
+    load(path).into_iter().rev().find(|value| value.key == key)
+}
+
+pub fn get(path: &str, key: &str) -> Option<String> {
+    lookup(Some(path), key).map(|value| value.value)
+}
+
+// author: `name <email>` from user.name and user.email, the login name when no identity is configured
+pub fn author(path: &str) -> String {
+    let name = get(path, "user.name").filter(|name| !name.is_empty());
+    let email = get(path, "user.email").filter(|email| !email.is_empty());
+    match (name, email) {
+        (Some(name), Some(email)) => format!("{} <{}>", name, email),
+        (Some(name), None) => name,
+        (None, email) => {
+            let login = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_else(|_| "unknown".to_string());
+            email.map(|email| format!("{} <{}>", login, email)).unwrap_or(login)
+        },
+    }
+}
+
+// default_branch: init.defaultbranch of the user and system layers, main when it is not set
+pub fn default_branch() -> String {
+    lookup(None, "init.defaultbranch").map(|value| value.value)
+        .filter(|branch| !branch.is_empty())
+        .unwrap_or_else(|| layout::DEFAULT_BRANCH.to_string())
+}
+
+// diff_context: diff.context, the number of unchanged lines around each change
+pub fn diff_context(path: &str, default: usize) -> usize {
+    get(path, "diff.context").and_then(|lines| lines.parse().ok()).unwrap_or(default)
+}
+
+// edit: replace (Some) or remove (None) a key in the INI file of a layer, keeping every other line
+fn edit(path: Option<&str>, layer: ConfigLayer, key: &str, value: Option<&str>) -> Result<String, String> {
+    let (section, name) = split_key(key).ok_or(format!("Invalid key {}, expected <section>.<name>", key))?;
+    let file_path = match layer.file_path(path) {
+        Some(file_path) => file_path,
+        None if layer == ConfigLayer::Repository => return Err("Not inside a repository, use --user or --system".to_string()),
+        None => return Err(format!("The {} layer has no file to write to", layer.name())),
+    };
+    let text = fs::read_to_string(&file_path).unwrap_or_default();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut section_end = None; // where a new line of the section goes
+    let mut found = false;
+    for line in text.lines() {
+        if let Some(header) = section_header(line) {
+            current = header;
+        } else if current == section && key_value(line).is_some_and(|(line_key, _)| line_key == name) {
+            // the first line of the key takes the new value, repeated ones are dropped
+            if let Some(value) = value.filter(|_| !found) {
+                lines.push(format!("\t{} = {}", name, quote(value)));
+            }
+            found = true;
+            section_end = Some(lines.len());
+            continue;
+        }
+        lines.push(line.to_string());
+        if current == section {
+            section_end = Some(lines.len());
+        }
+    }
+
+    match value {
+        Some(value) if !found => {
+            let line = format!("\t{} = {}", name, quote(value));
+            match section_end {
+                Some(end) => lines.insert(end, line),
+                None => {
+                    let header = match section.split_once('.') {
+                        Some((name, sub)) => format!("[{} \"{}\"]", name, sub),
+                        None => format!("[{}]", section),
+                    };
+                    lines.push(header);
+                    lines.push(line);
+                },
+            }
+        },
+        None if !found => return Err(format!("{} is not set in the {} config {}", key, layer.name(), file_path)),
+        _ => {},
+    }
+
+    if let Some(parent) = Path::new(&file_path).parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    fs::write(&file_path, lines.join("\n") + "\n").map_err(|err| format!("{}: {}", file_path, err))?;
+    Ok(file_path)
+}
+
+// values with spaces at either end or comment characters are quoted
+fn quote(value: &str) -> String {
+    if value.trim() != value || value.contains(['#', ';']) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn set(path: Option<&str>, layer: ConfigLayer, key: &str, value: &str) -> Result<String, String> {
+    let file_path = edit(path, layer, key, Some(value))?;
+    Ok(format!("Set {} = {} in the {} config {}", key, value, layer.name(), file_path))
+}
+
+pub fn unset(path: Option<&str>, layer: ConfigLayer, key: &str) -> Result<String, String> {
+    let file_path = edit(path, layer, key, None)?;
+    Ok(format!("Unset {} in the {} config {}", key, layer.name(), file_path))
+}
+
+// show: the winning value of a key and where it comes from
+pub fn show(path: Option<&str>, key: &str) -> Result<String, String> {
+    match lookup(path, key) {
+        Some(value) => Ok(format!("{}\t({} {})", value.value, value.layer.name(), value.origin)),
+        None => Err(format!("{} is not set", key)),
+    }
+}
+
+// list: every setting with its layer, a key set in several layers is listed once per layer, the last one wins
+pub fn list(path: Option<&str>) -> Result<String, String> {
+    let values = load(path);
+    if values.is_empty() {
+        return Ok("No settings".to_string());
+    }
+    Ok(values.iter()
+        .map(|value| format!("{}\t{}\t{}={}", value.layer.name(), value.origin, value.key, value.value))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> String {
+        let path = env::temp_dir().join(format!("mdv-config-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_ini() {
+        let values = parse_ini("# comment\n; comment\n[User]\n\tName = Jane Doe\n  email=\"  jane@example.com \"\n[remote \"Origin\"]\nurl = a\nurl = b\n[core]\nbare\n");
+        let expected: BTreeMap<String, String> = [
+            ("user.name", "Jane Doe"),
+            ("user.email", "  jane@example.com "),
+            ("remote.Origin.url", "b"),
+            ("core.bare", "true"),
+        ].iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_split_key() {
+        assert_eq!(split_key("user.name"), Some(("user".to_string(), "name".to_string())));
+        assert_eq!(split_key("User.Name"), Some(("user".to_string(), "name".to_string())));
+        assert_eq!(split_key("Remote.Origin.URL"), Some(("remote.Origin".to_string(), "url".to_string())));
+        assert_eq!(split_key("remote.my.host.url"), Some(("remote.my.host".to_string(), "url".to_string())));
+        for key in ["name", ".name", "user.", "us er.name", "user.na_me", ""] {
+            assert_eq!(split_key(key), None, "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_edit() {
+        let path = temp_dir("edit");
+        let file_path = layout::config_path(&path);
+        fs::create_dir_all(Path::new(&file_path).parent().unwrap()).unwrap();
+        fs::write(&file_path, "# settings\n[user]\n\tname = Old\n\temail = a@b\n\tname = Repeated\n[core]\n\tbare = false\n").unwrap();
+        let layer = ConfigLayer::Repository;
+
+        // the first line of a key takes the value, repeated lines go, everything else stays
+        assert!(set(Some(&path), layer, "user.name", "New Name").is_ok());
+        // a new key goes to the end of its section, a new subsection gets its own header
+        assert!(set(Some(&path), layer, "user.signing", " spaced ").is_ok());
+        assert!(set(Some(&path), layer, "remote.Origin.url", "https://example.com/#repo").is_ok());
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "# settings\n[user]\n\tname = New Name\n\temail = a@b\n\tsigning = \" spaced \"\n[core]\n\tbare = false\n[remote \"Origin\"]\n\turl = \"https://example.com/#repo\"\n",
+        );
+        assert_eq!(get(&path, "user.signing").as_deref(), Some(" spaced "));
+        assert_eq!(get(&path, "REMOTE.Origin.URL").as_deref(), Some("https://example.com/#repo"));
+
+        assert!(unset(Some(&path), layer, "user.email").is_ok());
+        assert_eq!(fs::read_to_string(&file_path).unwrap().lines().filter(|line| line.contains("email")).count(), 0);
+        assert!(unset(Some(&path), layer, "user.email").unwrap_err().starts_with("user.email is not set in the repository config"));
+        assert!(set(Some(&path), layer, "nosection", "x").is_err());
+        assert_eq!(set(None, layer, "user.name", "x"), Err("Not inside a repository, use --user or --system".to_string()));
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_layers() {
+        // the keys are only used here, the other tests read the same environment
+        let path = temp_dir("layers");
+        let system = format!("{}/system", path);
+        let user = format!("{}/user", path);
+        fs::write(&system, "[test]\n\tsystem = s\n\tuser = s\n\trepo = s\n\tenv = s\n").unwrap();
+        fs::write(&user, "[test]\n\tuser = u\n\trepo = u\n\tenv = u\n").unwrap();
+        fs::create_dir_all(layout::mdv_dir(&path)).unwrap();
+        fs::write(layout::config_path(&path), "[test]\n\trepo = r\n\tenv = r\n").unwrap();
+        env::set_var("MDV_CONFIG_SYSTEM", &system);
+        env::set_var("MDV_CONFIG_GLOBAL", &user);
+        env::set_var("MDV_CONFIG_COUNT", "2");
+        env::set_var("MDV_CONFIG_KEY_0", "Test.Env");
+        env::set_var("MDV_CONFIG_VALUE_0", "e");
+        env::set_var("MDV_CONFIG_KEY_1", "invalid");
+        env::set_var("MDV_CONFIG_VALUE_1", "ignored");
+
+        let layer_of = |key: &str| lookup(Some(&path), key).map(|value| (value.value, value.layer));
+        assert_eq!(layer_of("test.system"), Some(("s".to_string(), ConfigLayer::System)));
+        assert_eq!(layer_of("test.user"), Some(("u".to_string(), ConfigLayer::User)));
+        assert_eq!(layer_of("test.repo"), Some(("r".to_string(), ConfigLayer::Repository)));
+        assert_eq!(layer_of("test.env"), Some(("e".to_string(), ConfigLayer::Environment)));
+        // without a repository the repository layer is left out
+        assert_eq!(lookup(None, "test.repo").map(|value| value.layer), Some(ConfigLayer::User));
+        assert!(show(Some(&path), "test.repo").unwrap().starts_with("r\t(repository "));
+        let listed: Vec<String> = list(Some(&path)).unwrap().lines().filter(|line| line.contains("test.env=")).map(|line| line.split('\t').next().unwrap().to_string()).collect();
+        assert_eq!(listed, vec!["system", "user", "repository", "environment"]);
+
+        for name in ["MDV_CONFIG_SYSTEM", "MDV_CONFIG_GLOBAL", "MDV_CONFIG_COUNT", "MDV_CONFIG_KEY_0", "MDV_CONFIG_VALUE_0", "MDV_CONFIG_KEY_1", "MDV_CONFIG_VALUE_1"] {
+            env::remove_var(name);
+        }
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
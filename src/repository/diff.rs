@@ -0,0 +1,442 @@
+use crate::repository::layout;
+use crate::repository::objects;
+use crate::repository::revision::{open_head, open_staging_area, relative_path, resolve_revision, revision_tree, short_id};
+use crate::repository::tree;
+use std::collections::BTreeMap;
+use std::fs;
+
+pub const DEFAULT_CONTEXT: usize = 3;
+
+// one step of an edit script, indexes point into the old and new lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+// myers_diff: shortest edit script turning `old` into `new` (Myers, "An O(ND) Difference Algorithm"),
+// in linear space: the middle snake of an optimal path splits the problem in two, solved the same way
+pub fn myers_diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    diff_range(old, new, (0, old.len()), (0, new.len()), &mut edits);
+    edits
+}
+
+// diff_range: the edits between old[start..end] and new[start..end], appended in order
+fn diff_range<T: PartialEq>(old: &[T], new: &[T], (mut a0, mut a1): (usize, usize), (mut b0, mut b1): (usize, usize), edits: &mut Vec<Edit>) {
+    // common prefix and suffix are equal lines, only the middle needs a search
+    while a0 < a1 && b0 < b1 && old[a0] == new[b0] {
+        edits.push(Edit::Equal(a0, b0));
+        a0 += 1;
+        b0 += 1;
+    }
+    let mut suffix = 0;
+    while a0 < a1 && b0 < b1 && old[a1 - 1] == new[b1 - 1] {
+        a1 -= 1;
+        b1 -= 1;
+        suffix += 1;
+    }
+
+    if a0 == a1 {
+        edits.extend((b0..b1).map(Edit::Insert));
+    } else if b0 == b1 {
+        edits.extend((a0..a1).map(Edit::Delete));
+    } else {
+        match middle_snake(&old[a0..a1], &new[b0..b1]) {
+            Some((x, y)) => {
+                diff_range(old, new, (a0, a0 + x), (b0, b0 + y), edits);
+                diff_range(old, new, (a0 + x, a1), (b0 + y, b1), edits);
+            },
+            // nothing in common
+            None => {
+                edits.extend((a0..a1).map(Edit::Delete));
+                edits.extend((b0..b1).map(Edit::Insert));
+            },
+        }
+    }
+    edits.extend((0..suffix).map(|i| Edit::Equal(a1 + i, b1 + i)));
+}
+
+// middle_snake: a point on a shortest edit path, found by searching from both ends until the paths meet.
+// v[k] is the furthest x reached on diagonal k, forward from the start or backward from the end.
+fn middle_snake<T: PartialEq>(old: &[T], new: &[T]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let length = 2 * max_d + 2;
+    let mut forward = vec![-1isize; length as usize];
+    let mut backward = vec![-1isize; length as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // with an odd delta the forward path reaches the backward one first, with an even one the other way round
+    let front = delta % 2 != 0;
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        for k1 in (-d + k1_start..=d - k1_end).step_by(2) {
+            let k1_offset = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[k1_offset - 1] < forward[k1_offset + 1]) {
+                forward[k1_offset + 1]
+            } else {
+                forward[k1_offset - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && old[x1 as usize] == new[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[k1_offset] = x1;
+            if x1 > n {
+                k1_end += 2; // ran off the right
+            } else if y1 > m {
+                k1_start += 2; // ran off the bottom
+            } else if front {
+                let k2_offset = offset + delta - k1;
+                if (0..length).contains(&k2_offset) && backward[k2_offset as usize] != -1 && x1 >= n - backward[k2_offset as usize] {
+                    return Some((x1 as usize, y1 as usize));
+                }
+            }
+        }
+
+        for k2 in (-d + k2_start..=d - k2_end).step_by(2) {
+            let k2_offset = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[k2_offset - 1] < backward[k2_offset + 1]) {
+                backward[k2_offset + 1]
+            } else {
+                backward[k2_offset - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && old[(n - x2 - 1) as usize] == new[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[k2_offset] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !front {
+                let k1_offset = offset + delta - k2;
+                if (0..length).contains(&k1_offset) && forward[k1_offset as usize] != -1 {
+                    let x1 = forward[k1_offset as usize];
+                    let y1 = offset + x1 - k1_offset;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// split_lines: the lines of a text with their line ends, so that a change of `\r\n` or of the final
+// newline is a change of the line
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+// a line of a hunk without its line end, a last line without one is marked as in unified diffs
+fn hunk_line(prefix: char, line: &str) -> String {
+    match line.strip_suffix('\n') {
+        Some(text) => format!("{}{}", prefix, text),
+        None => format!("{}{}\n\\ No newline at end of file", prefix, line),
+    }
+}
+
+// is_binary: content that is not text, with a NUL byte near the start (as git checks) or invalid UTF-8
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|byte| *byte == 0) || std::str::from_utf8(content).is_err()
+}
+
+// size and short hash of one side of a binary diff
+fn binary_summary(header: &str, file: Option<&FileRef>, content: &[u8]) -> String {
+    match file {
+        Some(file) => format!("{} ({} bytes, {})", header, content.len(), short_id(&file.id)),
+        None => header.to_string(),
+    }
+}
+
+// unified_hunks: the `@@` hunks of a unified diff with `context` unchanged lines around every change
+pub fn unified_hunks(old: &[&str], new: &[&str], context: usize) -> Vec<String> {
+    let edits = myers_diff(old, new);
+    let changes: Vec<usize> = edits.iter().enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return Vec::new();
+    }
+
+    // group changes whose context would overlap into the same hunk
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(edits.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    // line numbers (0-based) of the old and new side before each edit
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in &edits {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Equal(_, _) => { old_line += 1; new_line += 1; },
+            Edit::Delete(_) => old_line += 1,
+            Edit::Insert(_) => new_line += 1,
+        }
+    }
+
+    ranges.into_iter().map(|(start, end)| {
+        let mut lines = Vec::new();
+        let (mut old_count, mut new_count) = (0, 0);
+        for edit in &edits[start..end] {
+            match *edit {
+                Edit::Equal(i, _) => { lines.push(hunk_line(' ', old[i])); old_count += 1; new_count += 1; },
+                Edit::Delete(i) => { lines.push(hunk_line('-', old[i])); old_count += 1; },
+                Edit::Insert(j) => { lines.push(hunk_line('+', new[j])); new_count += 1; },
+            }
+        }
+        let (old_start, new_start) = positions[start];
+        let old_start = if old_count == 0 { old_start } else { old_start + 1 };
+        let new_start = if new_count == 0 { new_start } else { new_start + 1 };
+        format!("@@ -{},{} +{},{} @@\n{}", old_start, old_count, new_start, new_count, lines.join("\n"))
+    }).collect()
+}
+
+// a file of one side of a diff: its blob id, and where to read it from when it is not in the object store
+#[derive(Debug, Clone)]
+struct FileRef {
+    id: String,
+    disk_path: Option<String>,
+}
+
+impl FileRef {
+    fn load(&self, path: &str) -> Result<Vec<u8>, String> {
+        match &self.disk_path {
+            Some(disk_path) => fs::read(disk_path).map_err(|err| format!("{}: {}", disk_path, err)),
+            None => objects::read_object(path, &self.id),
+        }
+    }
+}
+
+// a side of a diff: a revision, the staging area (the next commit) or the working directory
+pub enum DiffSource {
+    Revision(String),
+    Staged,
+    Working,
+}
+
+pub fn parse_source(path: &str, name: &str) -> Result<DiffSource, String> {
+    match name {
+        "--staged" => Ok(DiffSource::Staged),
+        "--working" => Ok(DiffSource::Working),
+        _ => resolve_revision(path, name).map(|(id, _)| DiffSource::Revision(id)),
+    }
+}
+
+fn source_label(source: &DiffSource) -> String {
+    match source {
+        DiffSource::Revision(id) => id.chars().take(8).collect(),
+        DiffSource::Staged => "staging area".to_string(),
+        DiffSource::Working => "working directory".to_string(),
+    }
+}
+
+fn tree_files(path: &str, tree_id: Option<String>) -> Result<BTreeMap<String, FileRef>, String> {
+    let files = match tree_id {
+        Some(tree_id) => tree::flatten(path, &tree_id)?,
+        None => BTreeMap::new(),
+    };
+    Ok(files.into_iter().map(|(relative, file)| (relative, FileRef { id: file.blob, disk_path: None })).collect())
+}
+
+fn disk_file(path: &str, relative: &str) -> Option<FileRef> {
+    let disk_path = format!("{}/{}", path, relative);
+    objects::hash_file(&disk_path).map(|id| FileRef { id, disk_path: Some(disk_path) })
+}
+
+fn snapshot(path: &str, source: &DiffSource) -> Result<BTreeMap<String, FileRef>, String> {
+    if let DiffSource::Revision(id) = source {
+        return tree_files(path, revision_tree(path, id));
+    }
+
+    let head_file = open_head(&layout::head_path(path));
+    let staging_area = open_staging_area(path)?;
+    let mut files = tree_files(path, revision_tree(path, head_file.get_revision_id()))?;
+    let staged: Vec<String> = staging_area.get_tracked_files().iter().map(|file_path| relative_path(path, file_path)).collect();
+
+    // the staging area is the head revision with the staged files replaced by their snapshots,
+    // the working directory is every tracked or staged file as it is on disk
+    if let DiffSource::Staged = source {
+        for relative in staged {
+            match staging_area.staged_file(path, &format!("{}/{}", path, relative))? {
+                Some(file) => { files.insert(relative, FileRef { id: file.blob, disk_path: None }); },
+                None => { files.remove(&relative); },
+            }
+        }
+        return Ok(files);
+    }
+    for relative in files.keys().cloned().chain(staged).collect::<Vec<String>>() {
+        match disk_file(path, &relative) {
+            Some(file) => { files.insert(relative, file); },
+            None => { files.remove(&relative); },
+        }
+    }
+    Ok(files)
+}
+
+// diff: list added, removed and modified files between two sources with their hunks
+pub fn diff(path: &str, from: &str, to: &str, context: usize) -> Result<String, String> {
+    let from_source = parse_source(path, from)?;
+    let to_source = parse_source(path, to)?;
+    let old_files = snapshot(path, &from_source)?;
+    let new_files = snapshot(path, &to_source)?;
+
+    let mut names: Vec<&String> = old_files.keys().chain(new_files.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let mut output = Vec::new();
+    for name in names {
+        let old_file = old_files.get(name);
+        let new_file = new_files.get(name);
+        let (label, old_header, new_header) = match (old_file, new_file) {
+            (Some(old), Some(new)) if old.id == new.id => continue,
+            (Some(_), Some(_)) => ("modified", format!("a/{}", name), format!("b/{}", name)),
+            (None, Some(_)) => ("added", "/dev/null".to_string(), format!("b/{}", name)),
+            (Some(_), None) => ("removed", format!("a/{}", name), "/dev/null".to_string()),
+            (None, None) => continue,
+        };
+        let old_content = old_file.map(|file| file.load(path)).transpose()?.unwrap_or_default();
+        let new_content = new_file.map(|file| file.load(path)).transpose()?.unwrap_or_default();
+
+        output.push(format!("{}: {}\n--- {}\n+++ {}", label, name, old_header, new_header));
+        // binary content has no lines to compare, only its size and hash are shown
+        if is_binary(&old_content) || is_binary(&new_content) {
+            output.push(format!("Binary files differ: {} -> {}",
+                binary_summary(&old_header, old_file, &old_content), binary_summary(&new_header, new_file, &new_content)));
+            continue;
+        }
+        let old_text = String::from_utf8_lossy(&old_content);
+        let new_text = String::from_utf8_lossy(&new_content);
+        output.extend(unified_hunks(&split_lines(&old_text), &split_lines(&new_text), context));
+    }
+
+    if output.is_empty() {
+        Ok(format!("No differences between {} and {}", source_label(&from_source), source_label(&to_source)))
+    } else {
+        Ok(output.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // applies an edit script to `old`, checking that it walks both sides in order
+    fn replay<T: PartialEq + Clone + std::fmt::Debug>(old: &[T], new: &[T], edits: &[Edit]) -> Vec<T> {
+        let (mut i, mut j) = (0, 0);
+        let mut result = Vec::new();
+        for edit in edits {
+            match *edit {
+                Edit::Equal(a, b) => {
+                    assert_eq!((a, b), (i, j));
+                    assert_eq!(old[a], new[b]);
+                    result.push(old[a].clone());
+                    i += 1;
+                    j += 1;
+                },
+                Edit::Delete(a) => { assert_eq!(a, i); i += 1; },
+                Edit::Insert(b) => { assert_eq!(b, j); result.push(new[b].clone()); j += 1; },
+            }
+        }
+        assert_eq!((i, j), (old.len(), new.len()));
+        result
+    }
+
+    fn changes(edits: &[Edit]) -> usize {
+        edits.iter().filter(|edit| !matches!(edit, Edit::Equal(_, _))).count()
+    }
+
+    #[test]
+    fn test_myers_diff_edge_cases() {
+        let empty: [char; 0] = [];
+        assert!(myers_diff(&empty, &empty).is_empty());
+        assert_eq!(myers_diff(&empty, &['a', 'b']), vec![Edit::Insert(0), Edit::Insert(1)]);
+        assert_eq!(myers_diff(&['a', 'b'], &empty), vec![Edit::Delete(0), Edit::Delete(1)]);
+        assert_eq!(myers_diff(&['a', 'b'], &['a', 'b']), vec![Edit::Equal(0, 0), Edit::Equal(1, 1)]);
+        assert_eq!(changes(&myers_diff(&['a', 'b'], &['c', 'd'])), 4);
+    }
+
+    #[test]
+    fn test_myers_diff_is_shortest() {
+        // the classic example of the paper: ABCABBA -> CBABAC takes 5 edits
+        let old: Vec<char> = "ABCABBA".chars().collect();
+        let new: Vec<char> = "CBABAC".chars().collect();
+        let edits = myers_diff(&old, &new);
+        assert_eq!(changes(&edits), 5);
+        assert_eq!(replay(&old, &new, &edits), new);
+
+        let old: Vec<char> = "abcdefg".chars().collect();
+        let new: Vec<char> = "axcdyfgz".chars().collect();
+        let edits = myers_diff(&old, &new);
+        assert_eq!(changes(&edits), 5);
+        assert_eq!(replay(&old, &new, &edits), new);
+    }
+
+    #[test]
+    fn test_myers_diff_large_rewrite() {
+        // every line differs: the linear-space search has to handle the worst case without a huge trace
+        let old: Vec<String> = (0..3000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..3000).map(|i| format!("new {}", i)).collect();
+        let edits = myers_diff(&old, &new);
+        assert_eq!(changes(&edits), 6000);
+        assert_eq!(replay(&old, &new, &edits), new);
+    }
+
+    #[test]
+    fn test_split_lines_keeps_line_ends() {
+        assert_eq!(split_lines("a\r\nb\nc"), vec!["a\r\n", "b\n", "c"]);
+        assert_eq!(split_lines("a\n"), vec!["a\n"]);
+        assert!(split_lines("").is_empty());
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let old = split_lines("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+        let new = split_lines("1\n2\nthree\n4\n5\n6\n7\n8\n9\nten\n");
+        // far apart changes get a hunk each
+        let hunks = unified_hunks(&old, &new, 1);
+        assert_eq!(hunks, vec!["@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4", "@@ -9,2 +9,2 @@\n 9\n-10\n+ten"]);
+        // overlapping context joins them
+        assert_eq!(unified_hunks(&old, &new, 3).len(), 1);
+        assert!(unified_hunks(&old, &old, 3).is_empty());
+        // a new file starts at line 0 of the old side
+        assert_eq!(unified_hunks(&[], &["a\n"], 3), vec!["@@ -0,0 +1,1 @@\n+a"]);
+    }
+
+    #[test]
+    fn test_unified_hunks_line_ends() {
+        // only the final newline changes
+        let hunks = unified_hunks(&split_lines("a\nb"), &split_lines("a\nb\n"), 3);
+        assert_eq!(hunks, vec!["@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b"]);
+        // only the line ends change
+        let hunks = unified_hunks(&split_lines("a\nb\n"), &split_lines("a\r\nb\n"), 0);
+        assert_eq!(hunks, vec!["@@ -1,1 +1,1 @@\n-a\n+a\r"]);
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(!is_binary("Unicode ü 日本\n".as_bytes()));
+        assert!(is_binary(b"a\0b"));
+        assert!(is_binary(&[0xff, 0xfe, 0x41]));
+    }
+}
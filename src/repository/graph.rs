@@ -0,0 +1,151 @@
+use crate::repository::layout;
+use crate::repository::merge::parents;
+use crate::repository::refs;
+use crate::repository::revision::{open_revision, short_id, Revision};
+use chrono::DateTime;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+// The graph is drawn one lane per line of history, two characters wide:
+//
+// *   revision on lane 0, `|` for the lanes passing by
+// |\  a merge opens a lane for its branch parent next to it
+// | * revision on lane 1
+// |/  the lane joins lane 0 again, both lead to the same revision
+// *
+//
+// Lanes only ever move by one position between two rows, except when they join a lane further left,
+// then the gap is bridged with `_`.
+
+// topo_order: revisions reachable from `starts`, every revision before its parents, newer ones first
+pub fn topo_order(path: &str, starts: &[String]) -> Vec<Revision> {
+    let mut revisions: HashMap<String, Revision> = HashMap::new();
+    let mut children: HashMap<String, usize> = HashMap::new();
+    let mut queue: VecDeque<String> = starts.iter().cloned().collect();
+    while let Some(id) = queue.pop_front() {
+        if revisions.contains_key(&id) {
+            continue;
+        }
+        for parent in parents(path, &id) {
+            *children.entry(parent.clone()).or_default() += 1;
+            queue.push_back(parent);
+        }
+        revisions.insert(id.clone(), open_revision(&layout::revision_path(path, &id)));
+    }
+
+    let date = |rev: &Revision| DateTime::parse_from_rfc3339(rev.get_date()).ok();
+    let mut ready: BinaryHeap<_> = revisions.iter()
+        .filter(|(id, _)| !children.contains_key(*id))
+        .map(|(id, rev)| (date(rev), id.clone()))
+        .collect();
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    while let Some((_, id)) = ready.pop() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let rev = revisions.remove(&id).unwrap();
+        for parent in parents(path, &id) {
+            let remaining = children.get_mut(&parent).unwrap();
+            *remaining -= 1;
+            if *remaining == 0 {
+                ready.push((date(&revisions[&parent]), parent));
+            }
+        }
+        order.push(rev);
+    }
+    order
+}
+
+// transition: the row joining one set of lanes to the next, `moves` lists (from lane, to lane) pairs
+fn transition(moves: &[(usize, usize)]) -> String {
+    let width = moves.iter().map(|(from, to)| 2 * from.max(to) + 2).max().unwrap_or(0);
+    let mut row = vec![' '; width];
+    for &(from, to) in moves {
+        if to == from {
+            row[2 * from] = '|';
+        } else if to > from {
+            row[2 * from + 1] = '\\';
+        } else {
+            row[2 * from - 1] = '/';
+            for cell in row.iter_mut().take(2 * from - 1).skip(2 * to + 1) {
+                if *cell == ' ' {
+                    *cell = '_';
+                }
+            }
+        }
+    }
+    row.into_iter().collect::<String>().trim_end().to_string()
+}
+
+fn lane_row(lanes: &[String], current: usize) -> String {
+    lanes.iter().enumerate()
+        .map(|(lane, _)| if lane == current { "* " } else { "| " })
+        .collect::<String>()
+}
+
+// graph: one line per revision with the lanes, short id, labels and first line of the message
+pub fn graph(path: &str, starts: &[String], max_count: Option<usize>) -> Vec<String> {
+    let labels = refs::labels(path);
+    let mut lanes: Vec<String> = Vec::new(); // revision each lane leads to
+    let mut output = Vec::new();
+
+    for rev in topo_order(path, starts).into_iter().take(max_count.unwrap_or(usize::MAX)) {
+        let id = rev.get_id();
+        let current = match lanes.iter().position(|lane| *lane == id) {
+            Some(lane) => lane,
+            None => {
+                lanes.push(id.clone());
+                lanes.len() - 1
+            },
+        };
+
+        // other lanes leading to this revision join the current one
+        if lanes.iter().skip(current + 1).any(|lane| *lane == id) {
+            let mut moves = Vec::new();
+            let mut joined = 0;
+            for (lane, target) in lanes.iter().enumerate() {
+                if lane > current && *target == id {
+                    moves.push((lane, current));
+                    joined += 1;
+                } else {
+                    moves.push((lane, lane - joined));
+                }
+            }
+            output.push(transition(&moves));
+            let mut index = 0;
+            lanes.retain(|target| {
+                index += 1;
+                index - 1 <= current || *target != id
+            });
+        }
+
+        let names = labels.get(&id).map(|names| format!(" ({})", names.join(", "))).unwrap_or_default();
+        let message = rev.get_message().lines().next().unwrap_or("");
+        output.push(format!("{}{}{} {}", lane_row(&lanes, current), short_id(&id), names, message).trim_end().to_string());
+
+        // the lane continues with the main parent, a branch parent gets a new lane next to it
+        let rev_parents = parents(path, &id);
+        let mut moves: Vec<(usize, usize)> = (0..lanes.len()).map(|lane| (lane, lane)).collect();
+        match rev_parents.as_slice() {
+            [] => {
+                lanes.remove(current);
+                moves = (0..lanes.len() + 1).filter(|lane| *lane != current)
+                    .map(|lane| (lane, if lane > current { lane - 1 } else { lane }))
+                    .collect();
+            },
+            [main_parent] => lanes[current] = main_parent.clone(),
+            [main_parent, branch_parent, ..] => {
+                lanes[current] = main_parent.clone();
+                lanes.insert(current + 1, branch_parent.clone());
+                moves = (0..lanes.len() - 1)
+                    .map(|lane| (lane, if lane > current { lane + 1 } else { lane }))
+                    .chain([(current, current + 1)])
+                    .collect();
+            },
+        }
+        if moves.iter().any(|(from, to)| from != to) {
+            output.push(transition(&moves));
+        }
+    }
+    output
+}
@@ -0,0 +1,329 @@
+use crate::repository::layout;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+// .mdvignore files use the gitignore syntax, one pattern per line:
+//
+// # comment         blank lines and comments are skipped, \# and \! escape a leading # or !
+// *.log             no slash: matches a name at any depth below the folder of the .mdvignore file
+// /build, doc/*.md  a slash at the start or in the middle anchors the pattern to that folder
+// target/           a trailing slash only matches folders (and so everything inside them)
+// !keep.log         negation: a later match un-ignores the path again
+// *, ?, [a-z], **   globs, * and ? never match a slash, ** matches across folders
+//
+// Files in subfolders add rules for their folder, the last matching rule wins and deeper files come last.
+// A file inside an ignored folder cannot be un-ignored, as in git.
+
+pub const IGNORE_FILE: &str = ".mdvignore";
+
+#[derive(Debug, Clone)]
+pub struct IgnoreRule {
+    pub source: String,  // ignore file, relative to the repository
+    pub line: usize,
+    pub pattern: String, // the line as written
+    pub negated: bool,
+    dir_only: bool,
+    base: String,        // folder of the ignore file, "" for the root
+    regex: Regex,
+}
+
+impl IgnoreRule {
+    // parse: a rule from one line of an ignore file, None for blank lines and comments
+    pub fn parse(line: &str, source: &str, line_number: usize, base: &str) -> Option<IgnoreRule> {
+        let pattern = line.trim_end_matches(['\r', '\n']);
+        // trailing spaces are dropped unless escaped
+        let mut text = pattern.trim_end_matches(' ').to_string();
+        if text.ends_with('\\') && pattern.len() > text.len() {
+            text.push(' ');
+        }
+        if text.is_empty() || text.starts_with('#') {
+            return None;
+        }
+
+        let negated = text.starts_with('!');
+        let mut glob = if negated { &text[1..] } else { &text[..] };
+        if glob.starts_with("\\#") || glob.starts_with("\\!") {
+            glob = &glob[1..];
+        }
+        let dir_only = glob.ends_with('/');
+        let glob = glob.trim_end_matches('/');
+        if glob.is_empty() {
+            return None;
+        }
+        let regex = pattern_regex(glob)?;
+        Some(IgnoreRule {
+            source: source.to_string(),
+            line: line_number,
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            base: base.to_string(),
+            regex,
+        })
+    }
+
+    pub fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let inside = if self.base.is_empty() {
+            Some(relative)
+        } else {
+            relative.strip_prefix(&self.base).and_then(|rest| rest.strip_prefix('/'))
+        };
+        inside.is_some_and(|inside| self.regex.is_match(inside))
+    }
+}
+
+// glob_to_regex: the regular expression for a gitignore glob, without anchors
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                if at_start && chars.get(i + 2) == Some(&'/') {
+                    regex.push_str("(?:.*/)?"); // **/ : any number of folders
+                    i += 3;
+                } else {
+                    regex.push_str(".*"); // trailing /** or ** in a name
+                    i += 2;
+                }
+                continue;
+            },
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|c| *c == ']') {
+                Some(length) if length > 0 => {
+                    let class: String = chars[i + 1..i + 1 + length].iter().collect();
+                    let class = class.strip_prefix('!').map(|rest| format!("^{}", rest)).unwrap_or(class);
+                    regex.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+                    i += length + 2;
+                    continue;
+                },
+                _ => regex.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            },
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
+
+// pattern_regex: a gitignore-style pattern, matching a name at any depth unless it contains a slash
+pub fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let anchored = pattern.contains('/');
+    let glob = pattern.trim_start_matches('/');
+    let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+    Regex::new(&format!("{}{}$", prefix, glob_to_regex(glob))).ok()
+}
+
+// glob_regex: a regular expression matching whole relative paths against a glob
+pub fn glob_regex(glob: &str) -> Option<Regex> {
+    Regex::new(&format!("^{}$", glob_to_regex(glob))).ok()
+}
+
+// the rules of every ignore file in a repository, in the order they apply
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    // load: read the ignore files of the repository, from the root down, skipping ignored folders
+    pub fn load(path: &str) -> IgnoreRules {
+        let mut ignore = IgnoreRules::default();
+        ignore.load_dir(path, "");
+        ignore
+    }
+
+    fn load_dir(&mut self, path: &str, relative_dir: &str) {
+        let dir = if relative_dir.is_empty() { path.to_string() } else { format!("{}/{}", path, relative_dir) };
+        let source = join(relative_dir, IGNORE_FILE);
+        if let Ok(content) = fs::read_to_string(format!("{}/{}", dir, IGNORE_FILE)) {
+            for (index, line) in content.lines().enumerate() {
+                if let Some(rule) = IgnoreRule::parse(line, &source, index + 1, relative_dir) {
+                    self.rules.push(rule);
+                }
+            }
+        }
+        for name in sorted_entries(&dir) {
+            let relative = join(relative_dir, &name);
+            if Path::new(&format!("{}/{}", dir, name)).is_dir() && !is_internal(&relative) && self.matching_rule(&relative, true).is_none() {
+                self.load_dir(path, &relative);
+            }
+        }
+    }
+
+    // check: the rule deciding about a path, a negated rule means it is explicitly not ignored.
+    // When a parent folder is ignored, its rule is the answer.
+    pub fn check(&self, relative: &str, is_dir: bool) -> Option<&IgnoreRule> {
+        let parts: Vec<&str> = relative.split('/').collect();
+        for end in 1..parts.len() {
+            if let Some(rule) = self.matching_rule(&parts[..end].join("/"), true) {
+                return Some(rule);
+            }
+        }
+        self.last_match(relative, is_dir)
+    }
+
+    pub fn is_ignored(&self, relative: &str, is_dir: bool) -> bool {
+        is_internal(relative) || self.check(relative, is_dir).is_some_and(|rule| !rule.negated)
+    }
+
+    fn last_match(&self, relative: &str, is_dir: bool) -> Option<&IgnoreRule> {
+        self.rules.iter().rev().find(|rule| rule.matches(relative, is_dir))
+    }
+
+    // the rule ignoring a path on its own, negations do not count
+    fn matching_rule(&self, relative: &str, is_dir: bool) -> Option<&IgnoreRule> {
+        self.last_match(relative, is_dir).filter(|rule| !rule.negated)
+    }
+}
+
+// the repository folder itself is always left out, and so are the legacy `.rev` folder and its old backup
+fn is_internal(relative: &str) -> bool {
+    [layout::MDV_DIR, layout::LEGACY_DIR, layout::OLD_LEGACY_BACKUP].iter()
+        .any(|dir| relative == *dir || relative.starts_with(&format!("{}/", dir)))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() { name.to_string() } else { format!("{}/{}", dir, name) }
+}
+
+fn sorted_entries(dir: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+// working_files: every file of the working directory that is not ignored, relative to the repository.
+// Ignored folders are not entered.
+pub fn working_files(path: &str) -> Vec<String> {
+    let ignore = IgnoreRules::load(path);
+    let mut files = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(relative_dir) = pending.pop() {
+        let dir = if relative_dir.is_empty() { path.to_string() } else { format!("{}/{}", path, relative_dir) };
+        for name in sorted_entries(&dir) {
+            let relative = join(&relative_dir, &name);
+            let is_dir = Path::new(&format!("{}/{}", dir, name)).is_dir();
+            if ignore.is_ignored(&relative, is_dir) {
+                continue;
+            }
+            if is_dir {
+                pending.push(relative);
+            } else {
+                files.push(relative);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+// check_ignore: for each path, the rule that decides whether it is ignored
+pub fn check_ignore(path: &str, paths: &[&str]) -> Result<String, String> {
+    let ignore = IgnoreRules::load(path);
+    let lines: Vec<String> = paths.iter()
+        .map(|relative| {
+            let relative = relative.trim_start_matches("./").trim_end_matches('/');
+            let is_dir = Path::new(&format!("{}/{}", path, relative)).is_dir();
+            match ignore.check(relative, is_dir) {
+                Some(rule) if rule.negated => format!("{}:{}:{}\t{} (not ignored)", rule.source, rule.line, rule.pattern, relative),
+                Some(rule) => format!("{}:{}:{}\t{}", rule.source, rule.line, rule.pattern, relative),
+                None if is_internal(relative) => format!("{}\t{} (repository data)", relative.split('/').next().unwrap_or(relative), relative),
+                None => format!("{}: not ignored", relative),
+            }
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(line: &str) -> IgnoreRule {
+        IgnoreRule::parse(line, IGNORE_FILE, 1, "").unwrap()
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let matches = |glob: &str, path: &str| glob_regex(glob).unwrap().is_match(path);
+        assert!(matches("*.log", "a.log"));
+        assert!(!matches("*.log", "dir/a.log")); // * never crosses a slash
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?.txt", "ab.txt"));
+        assert!(matches("[a-c].md", "b.md"));
+        assert!(!matches("[!a-c].md", "b.md"));
+        assert!(matches("[!a-c].md", "d.md"));
+        assert!(matches("**/x.rs", "x.rs"));
+        assert!(matches("**/x.rs", "a/b/x.rs"));
+        assert!(matches("doc/**", "doc/a/b.md"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        // regex characters and escapes are literal
+        assert!(matches("a+b(1).txt", "a+b(1).txt"));
+        assert!(matches("\\*.txt", "*.txt"));
+        assert!(!matches("\\*.txt", "a.txt"));
+        assert!(matches("[", "["));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(IgnoreRule::parse("", IGNORE_FILE, 1, "").is_none());
+        assert!(IgnoreRule::parse("# comment", IGNORE_FILE, 1, "").is_none());
+        assert!(IgnoreRule::parse("/", IGNORE_FILE, 1, "").is_none());
+        assert!(rule("\\#hash").matches("#hash", false));
+        assert!(!rule("\\!bang").negated);
+        assert!(rule("\\!bang").matches("!bang", false));
+        assert!(rule("!keep.log").negated);
+        // trailing spaces are dropped unless escaped
+        assert!(rule("a.txt  ").matches("a.txt", false));
+        assert!(rule("a\\ ").matches("a ", false));
+    }
+
+    #[test]
+    fn test_matches() {
+        // without a slash: a name at any depth
+        assert!(rule("*.log").matches("a.log", false));
+        assert!(rule("*.log").matches("dir/sub/a.log", false));
+        // a leading or middle slash anchors the pattern
+        assert!(rule("/build").matches("build", true));
+        assert!(!rule("/build").matches("src/build", true));
+        assert!(rule("doc/*.md").matches("doc/a.md", false));
+        assert!(!rule("doc/*.md").matches("x/doc/a.md", false));
+        // a trailing slash only matches folders
+        assert!(rule("target/").matches("target", true));
+        assert!(!rule("target/").matches("target", false));
+        // rules of a nested ignore file only apply below its folder
+        let nested = IgnoreRule::parse("*.tmp", "sub/.mdvignore", 1, "sub").unwrap();
+        assert!(nested.matches("sub/a.tmp", false));
+        assert!(nested.matches("sub/deep/a.tmp", false));
+        assert!(!nested.matches("a.tmp", false));
+        assert!(!nested.matches("subway/a.tmp", false));
+    }
+
+    #[test]
+    fn test_check() {
+        let rules = IgnoreRules { rules: vec![rule("*.log"), rule("!keep.log"), rule("build/"), rule("!build/keep.txt")] };
+        assert!(rules.is_ignored("a.log", false));
+        assert!(!rules.is_ignored("keep.log", false));
+        assert_eq!(rules.check("keep.log", false).map(|rule| rule.negated), Some(true));
+        assert!(!rules.is_ignored("a.txt", false));
+        // a file inside an ignored folder cannot be un-ignored
+        assert!(rules.is_ignored("build/keep.txt", false));
+        assert!(rules.is_ignored(".mdv/index", false));
+        assert!(rules.is_ignored(".rev.bak/store/x", false));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::machine::file_log_management::FileLogManager;
+use crate::repository::config;
+use crate::repository::objects;
+use crate::repository::revision::{Head, Revision};
+use crate::repository::staging::StagingArea;
+use crate::repository::tree::{self, FileEntry, MODE_FILE};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+// The layout module owns the on-disk format of a repository, every other module asks it for paths:
+//
+// .mdv/config               repository format version and settings
+// .mdv/head.json            current branch and revision
+// .mdv/index                files staged for the next commit and stat data of the tracked files (see staging.rs)
+// .mdv/objects/<id>         blobs and trees, keyed by the SHA-256 of their content
+// .mdv/rev/<id>/revision.json
+// .mdv/refs/heads/<branch>  revision id each branch points to
+// .mdv/refs/tags/<tag>      revision id each tag names
+// .mdv/refs/stash           stash entries, newest first
+// .mdv/MERGE_HEAD           revision being merged, only while a merge has conflicts
+// .mdv/legacy.rev           backup of the `.rev` folder of a repository upgraded from the legacy format
+//
+// A bare repository has no working tree and keeps the same files directly in the repository folder.
+
+pub const MDV_DIR: &str = ".mdv";
+pub const LEGACY_DIR: &str = ".rev";
+// where older versions left the backup of an upgraded `.rev` folder, in the working tree
+pub const OLD_LEGACY_BACKUP: &str = ".rev.bak";
+pub const FORMAT_VERSION: u32 = 1;
+pub const DEFAULT_BRANCH: &str = "main";
+// staging area of repositories created before the binary index, next to the index
+pub const LEGACY_STAGING_FILE: &str = "staging_area.json";
+
+pub fn mdv_dir(path: &str) -> String {
+    if is_bare(path) {
+        path.to_string()
+    } else {
+        format!("{}/{}", path, MDV_DIR)
+    }
+}
+
+pub fn config_path(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "config")
+}
+
+pub fn head_path(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "head.json")
+}
+
+pub fn staging_path(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "index")
+}
+
+// revision being merged while the merge waits for conflicts to be resolved
+pub fn merge_head_path(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "MERGE_HEAD")
+}
+
+pub fn legacy_backup_dir(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "legacy.rev")
+}
+
+pub fn objects_dir(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "objects")
+}
+
+pub fn revisions_dir(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "rev")
+}
+
+pub fn revision_dir(path: &str, id: &str) -> String {
+    format!("{}/{}", revisions_dir(path), id)
+}
+
+pub fn revision_path(path: &str, id: &str) -> String {
+    format!("{}/{}", revision_dir(path, id), "revision.json")
+}
+
+pub fn refs_dir(path: &str) -> String {
+    format!("{}/{}", mdv_dir(path), "refs")
+}
+
+pub fn branches_dir(path: &str) -> String {
+    format!("{}/{}", refs_dir(path), "heads")
+}
+
+pub fn branch_path(path: &str, branch: &str) -> String {
+    format!("{}/{}", branches_dir(path), branch)
+}
+
+pub fn tags_dir(path: &str) -> String {
+    format!("{}/{}", refs_dir(path), "tags")
+}
+
+pub fn tag_path(path: &str, tag: &str) -> String {
+    format!("{}/{}", tags_dir(path), tag)
+}
+
+pub fn stash_path(path: &str) -> String {
+    format!("{}/{}", refs_dir(path), "stash")
+}
+
+pub fn is_repository(path: &str) -> bool {
+    Path::new(path).join(MDV_DIR).is_dir() || is_bare(path)
+}
+
+// a bare repository is a folder with the repository files but no `.mdv`; the `.mdv` folder of a
+// repository looks the same from inside, its config tells them apart
+pub fn is_bare(path: &str) -> bool {
+    let root = Path::new(path);
+    let structure = !root.join(MDV_DIR).exists() && root.join("config").is_file() && root.join("head.json").is_file() && root.join("objects").is_dir();
+    structure
+        && root.file_name().is_none_or(|name| name != MDV_DIR)
+        && fs::read_to_string(root.join("config")).is_ok_and(|text| config::parse_ini(&text).get("core.bare").is_none_or(|bare| bare != "false"))
+}
+
+// init_repository: create the complete skeleton of an empty repository.
+// With force an existing repository is reinitialized: whatever is missing is added and the format version
+// is rewritten, its revisions, refs, objects, index and settings are kept. With wipe all of them are deleted
+// first and the repository starts over empty, the working files are left alone.
+pub fn init_repository(path: &str, bare: bool, force: bool, wipe: bool) -> Result<String, String> {
+    let existed = is_repository(path);
+    if existed && !force && !wipe {
+        return Err(format!("A repository already exists in {}, use init --force to reinitialize it or init --wipe to start over", mdv_dir(path)));
+    }
+    if existed && is_bare(path) != bare {
+        return Err(format!("Cannot reinitialize {} as a {} repository", path, if bare { "bare" } else { "non-bare" }));
+    }
+
+    let repo_dir = if bare { path.to_string() } else { format!("{}/{}", path, MDV_DIR) };
+    if existed && wipe {
+        for name in ["config", "head.json", "index", LEGACY_STAGING_FILE, "MERGE_HEAD", "objects", "rev", "refs"] {
+            let entry = Path::new(&repo_dir).join(name);
+            let _ = if entry.is_dir() { fs::remove_dir_all(&entry) } else { fs::remove_file(&entry) };
+        }
+    }
+
+    for dir in ["objects", "rev", "refs/heads"] {
+        fs::create_dir_all(format!("{}/{}", repo_dir, dir)).map_err(|err| err.to_string())?;
+    }
+    let config_file = format!("{}/{}", repo_dir, "config");
+    if Path::new(&config_file).is_file() {
+        // the settings of the repository stay, only the format version is brought up to date
+        config::set(Some(path), config::ConfigLayer::Repository, "core.repositoryformatversion", &FORMAT_VERSION.to_string())?;
+    } else {
+        let config = format!("[core]\n\trepositoryformatversion = {}\n\tbare = {}\n", FORMAT_VERSION, bare);
+        fs::write(&config_file, config).map_err(|err| err.to_string())?;
+    }
+    let index_file = format!("{}/{}", repo_dir, "index");
+    if !Path::new(&index_file).exists() && !Path::new(&repo_dir).join(LEGACY_STAGING_FILE).exists() {
+        fs::write(&index_file, StagingArea::new().to_index(path)).map_err(|err| err.to_string())?;
+    }
+    // head.json last: it is what marks a bare folder as a repository
+    let head_file = format!("{}/{}", repo_dir, "head.json");
+    if !Path::new(&head_file).is_file() {
+        Head::new(String::new(), config::default_branch()).save_head(&head_file);
+    }
+
+    let kind = if bare { "bare repository" } else { "repository" };
+    match (existed, wipe) {
+        (true, true) => Ok(format!("Wiped and reinitialized empty {} in {}", kind, repo_dir)),
+        (true, false) => Ok(format!("Reinitialized existing {} in {}", kind, repo_dir)),
+        (false, _) => Ok(format!("Initialized empty {} in {}", kind, repo_dir)),
+    }
+}
+
+pub fn has_legacy_data(path: &str) -> bool {
+    Path::new(path).join(LEGACY_DIR).is_dir()
+}
+
+// upgrade_legacy: convert the snapshots and branches of a `.rev` repository into the `.mdv` layout.
+// Existing `.mdv` data wins: branches that already exist are kept, and so is the current head.
+// The old folder is kept as `.mdv/legacy.rev` once everything is converted.
+pub fn upgrade_legacy(path: &str) -> Result<String, String> {
+    let manager = FileLogManager::new(Path::new(path));
+    let mut converted: HashMap<String, String> = HashMap::new(); // snapshot id -> revision id
+    let mut skipped = Vec::new();
+
+    let fresh = !is_repository(path);
+    if fresh {
+        init_repository(path, false, false, false)?;
+    }
+
+    for branch in manager.list_branches().map_err(|err| err.to_string())? {
+        let history = manager.get_snapshot_history(&branch).map_err(|err| err.to_string())?;
+        let mut parent: Option<String> = None;
+        let mut parent_files: BTreeMap<String, FileEntry> = BTreeMap::new();
+
+        // oldest snapshot first, so every parent is converted before its children
+        for (snapshot_id, snapshot) in history.into_iter().rev() {
+            let files = legacy_files(path, &manager, &snapshot.directory.0)?;
+            if let Some(rev_id) = converted.get(&snapshot_id) {
+                parent = Some(rev_id.clone());
+                parent_files = files;
+                continue;
+            }
+
+            let related_files: Vec<String> = files.iter()
+                .filter(|(relative, file)| parent_files.get(*relative) != Some(*file))
+                .map(|(relative, _)| relative.clone())
+                .chain(parent_files.keys().filter(|relative| !files.contains_key(*relative)).cloned())
+                .collect();
+            let date = manager.get_snapshot_date(&snapshot_id).unwrap_or_default();
+            let rev = Revision::new_at(
+                path.to_string(),
+                parent.clone(),
+                None,
+                "unknown".to_string(),
+                snapshot.message.clone(),
+                related_files,
+                tree::build_tree(path, &files)?,
+                date,
+            );
+            rev.save_revision();
+
+            converted.insert(snapshot_id, rev.get_id());
+            parent = Some(rev.get_id());
+            parent_files = files;
+        }
+
+        match parent {
+            Some(tip) if !Path::new(&branch_path(path, &branch)).exists() => {
+                fs::write(branch_path(path, &branch), tip).map_err(|err| err.to_string())?;
+            },
+            Some(_) => skipped.push(branch),
+            None => {},
+        }
+    }
+
+    if fresh {
+        let branch = manager.current_branch().to_string();
+        let tip = fs::read_to_string(branch_path(path, &branch)).unwrap_or_default();
+        Head::new(tip.trim().to_string(), branch).save_head(&head_path(path));
+    }
+
+    let legacy_path = Path::new(path).join(LEGACY_DIR);
+    fs::rename(&legacy_path, legacy_backup_dir(path)).map_err(|err| err.to_string())?;
+
+    if skipped.is_empty() {
+        Ok(format!("Upgraded {} legacy snapshots into {}", converted.len(), MDV_DIR))
+    } else {
+        Ok(format!("Upgraded {} legacy snapshots into {}, kept existing branches: {}", converted.len(), MDV_DIR, skipped.join(", ")))
+    }
+}
+
+// files of a legacy directory, with their contents moved into the object store
+fn legacy_files(path: &str, manager: &FileLogManager, directory_id: &str) -> Result<BTreeMap<String, FileEntry>, String> {
+    let directory = manager.get_directory(directory_id).map_err(|err| err.to_string())?;
+    directory.files().iter()
+        .map(|(name, object_id)| {
+            let content = manager.get_object(&object_id.0).map_err(|err| err.to_string())?;
+            let blob = objects::write_object(path, &content)?;
+            Ok((name.clone(), FileEntry { mode: MODE_FILE, blob }))
+        })
+        .collect()
+}
@@ -0,0 +1,156 @@
+use crate::repository::graph;
+use crate::repository::layout;
+use crate::repository::merge::parents;
+use crate::repository::refs;
+use crate::repository::revision::{open_head, open_revision, short_id, Revision};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, TimeZone};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+// filters of the log command, every filter that is set has to match for a revision to be shown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogFilter {
+    pub max_count: Option<usize>,
+    pub author: Option<String>,
+    pub since: Option<String>, // date or RFC 3339 time, inclusive
+    pub until: Option<String>, // date or RFC 3339 time, inclusive
+    pub message: Option<String>,
+    pub path: Option<String>,  // file or folder, relative to the repository
+    pub all_parents: bool,     // also follow the branch_parent of merge revisions
+    pub all_branches: bool,    // start from every branch instead of the head only
+}
+
+// parse_date: an RFC 3339 time, a relative time (now, yesterday, <n>.<unit>.ago), or
+// a YYYY-MM-DD date taken as the start (or the end) of that local day
+pub fn parse_date(text: &str, end_of_day: bool) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time);
+    }
+    if let Some(time) = relative_date(text) {
+        return Ok(time);
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}, expected YYYY-MM-DD, yesterday or <n>.days.ago", text))?;
+    let time = if end_of_day { date.and_hms_opt(23, 59, 59) } else { date.and_hms_opt(0, 0, 0) }.unwrap();
+    Local.from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.fixed_offset())
+        .ok_or(format!("Invalid date: {}", text))
+}
+
+// relative_date: now, yesterday, or a count of minutes, hours, days, weeks, months or years
+// ago, written with dots or spaces (2.weeks.ago, 3 days ago)
+fn relative_date(text: &str) -> Option<DateTime<FixedOffset>> {
+    let now = Local::now().fixed_offset();
+    let words: Vec<&str> = text.split(['.', ' ']).filter(|word| !word.is_empty()).collect();
+    let (count, unit) = match words.as_slice() {
+        ["now"] => return Some(now),
+        ["yesterday"] => (1, "day"),
+        [count, unit, "ago"] => (count.parse::<i64>().ok()?, *unit),
+        _ => return None,
+    };
+    let minutes = match unit.trim_end_matches('s') {
+        "minute" => 1,
+        "hour" => 60,
+        "day" => 60 * 24,
+        "week" => 60 * 24 * 7,
+        "month" => 60 * 24 * 30,
+        "year" => 60 * 24 * 365,
+        _ => return None,
+    };
+    Some(now - Duration::minutes(count * minutes))
+}
+
+fn revision_date(rev: &Revision) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(rev.get_date()).ok()
+}
+
+// history: revisions reachable from `starts`, newest first. Only main parents are followed
+// unless all_parents is set, then merged lines of history are interleaved by date.
+pub fn history(path: &str, starts: &[String], all_parents: bool) -> Vec<Revision> {
+    let mut revisions = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = BinaryHeap::new();
+    let open = |id: &str| open_revision(&layout::revision_path(path, id));
+
+    for start in starts {
+        queue.push((revision_date(&open(start)), start.clone()));
+    }
+    while let Some((_, id)) = queue.pop() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let next = if all_parents { parents(path, &id) } else { parents(path, &id).into_iter().take(1).collect() };
+        for parent in next {
+            if !seen.contains(&parent) {
+                queue.push((revision_date(&open(&parent)), parent));
+            }
+        }
+        revisions.push(open(&id));
+    }
+    revisions
+}
+
+fn touches(rev: &Revision, filter_path: &str) -> bool {
+    let filter_path = filter_path.trim_end_matches('/');
+    rev.get_related_files().iter().any(|file| {
+        file == filter_path || file.strip_prefix(filter_path).is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+// matching: the revisions of the history that pass every filter
+pub fn matching(path: &str, starts: &[String], filter: &LogFilter) -> Result<Vec<Revision>, String> {
+    let since = filter.since.as_deref().map(|text| parse_date(text, false)).transpose()?;
+    let until = filter.until.as_deref().map(|text| parse_date(text, true)).transpose()?;
+
+    Ok(history(path, starts, filter.all_parents)
+        .into_iter()
+        .filter(|rev| filter.author.as_ref().is_none_or(|author| rev.get_author().contains(author.as_str())))
+        .filter(|rev| filter.message.as_ref().is_none_or(|text| rev.get_message().contains(text.as_str())))
+        .filter(|rev| filter.path.as_ref().is_none_or(|filter_path| touches(rev, filter_path)))
+        .filter(|rev| {
+            let date = revision_date(rev);
+            since.is_none_or(|since| date.is_some_and(|date| date >= since))
+                && until.is_none_or(|until| date.is_some_and(|date| date <= until))
+        })
+        .take(filter.max_count.unwrap_or(usize::MAX))
+        .collect())
+}
+
+// format_revision: a revision with the branches and tags pointing at it
+pub fn format_revision(rev: &Revision, labels: &HashMap<String, Vec<String>>) -> String {
+    let names = labels.get(&rev.get_id()).map(|names| format!(" ({})", names.join(", "))).unwrap_or_default();
+    let mut lines = vec![format!("revision {}{}", rev.get_id(), names)];
+    if let (Some(main_parent), Some(branch_parent)) = rev.get_parent_ids() {
+        lines.push(format!("Merge: {} {}", short_id(main_parent), short_id(branch_parent)));
+    }
+    lines.push(format!("Author: {}", rev.get_author()));
+    lines.push(format!("Date: {}", rev.get_date()));
+    lines.push(String::new());
+    lines.extend(rev.get_message().lines().map(|line| format!("    {}", line)));
+    lines.join("\n")
+}
+
+// log: the history of the head revision (or of every branch), newest first.
+// As a graph every parent is followed and only the count filter applies.
+pub fn log(path: &str, filter: &LogFilter, graph: bool) -> Result<String, String> {
+    let head_file = open_head(&layout::head_path(path));
+    let mut starts: Vec<String> = Vec::new();
+    if !head_file.get_revision_id().is_empty() {
+        starts.push(head_file.get_revision_id().to_string());
+    }
+    if filter.all_branches {
+        starts.extend(refs::list_branches(path).into_iter().map(|(_, tip)| tip));
+    }
+    if starts.is_empty() {
+        return Err("Nothing committed yet, the history is empty".to_string());
+    }
+
+    if graph {
+        return Ok(graph::graph(path, &starts, filter.max_count).join("\n"));
+    }
+    let revisions = matching(path, &starts, filter)?;
+    if revisions.is_empty() {
+        return Ok("No revisions match the given filters".to_string());
+    }
+    let labels = refs::labels(path);
+    Ok(revisions.iter().map(|rev| format_revision(rev, &labels)).collect::<Vec<String>>().join("\n\n"))
+}
@@ -0,0 +1,329 @@
+use crate::repository::diff::{is_binary, myers_diff, split_lines, Edit};
+use crate::repository::layout;
+use crate::repository::objects;
+use crate::repository::refs;
+use crate::repository::revision::{
+    open_head, open_revision, open_staging_area, resolve_revision, result_format, revision_tree, switch_tree, Revision,
+};
+use crate::repository::status::collect_status;
+use crate::repository::tree::{self, FileEntry};
+use chrono::DateTime;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+
+// parents of a revision, main parent first
+pub fn parents(path: &str, rev_id: &str) -> Vec<String> {
+    let rev = open_revision(&layout::revision_path(path, rev_id));
+    let (main_parent, branch_parent) = rev.get_parent_ids();
+    main_parent.into_iter().chain(branch_parent).cloned().collect()
+}
+
+// ancestors: every revision reachable from rev_id through both parents, rev_id included
+pub fn ancestors(path: &str, rev_id: &str) -> HashSet<String> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([rev_id.to_string()]);
+    while let Some(id) = queue.pop_front() {
+        if seen.insert(id.clone()) {
+            queue.extend(parents(path, &id));
+        }
+    }
+    seen
+}
+
+// merge_base: lowest common ancestor of two revisions. The walk from `theirs` stops at the first
+// common revisions on every path, and candidates that are ancestors of another candidate are dropped.
+pub fn merge_base(path: &str, ours: &str, theirs: &str) -> Option<String> {
+    let ours_ancestors = ancestors(path, ours);
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([theirs.to_string()]);
+    while let Some(id) = queue.pop_front() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        if ours_ancestors.contains(&id) {
+            candidates.push(id);
+        } else {
+            queue.extend(parents(path, &id));
+        }
+    }
+
+    let lowest: Vec<&String> = candidates.iter()
+        .filter(|candidate| !candidates.iter().any(|other| other != *candidate && ancestors(path, other).contains(*candidate)))
+        .collect();
+    // with several lowest candidates (criss-cross history) the most recent one is used
+    lowest.into_iter()
+        .max_by_key(|id| DateTime::parse_from_rfc3339(open_revision(&layout::revision_path(path, id)).get_date()).ok())
+        .cloned()
+}
+
+// result of a three-way merge of one file
+pub struct MergedText {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+// merge3: three-way line merge, hunks changed on both sides in different ways are wrapped in conflict markers
+pub fn merge3(base: &str, ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> MergedText {
+    // lines keep their line ends, a side changing only those (CRLF, the final newline) changes the line
+    let base_lines = split_lines(base);
+    let ours_lines = split_lines(ours);
+    let theirs_lines = split_lines(theirs);
+    let ours_match = matched_lines(&base_lines, &ours_lines);
+    let theirs_match = matched_lines(&base_lines, &theirs_lines);
+
+    let mut output: Vec<String> = Vec::new();
+    let mut conflicts = 0;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < base_lines.len() || j < ours_lines.len() || k < theirs_lines.len() {
+        // stable line: unchanged on both sides
+        if i < base_lines.len() && ours_match[i] == Some(j) && theirs_match[i] == Some(k) {
+            output.push(base_lines[i].to_string());
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+
+        // unstable chunk: up to the next base line both sides still have
+        let (next_i, next_j, next_k) = (i..base_lines.len())
+            .find_map(|index| match (ours_match[index], theirs_match[index]) {
+                (Some(o), Some(t)) => Some((index, o, t)),
+                _ => None,
+            })
+            .unwrap_or((base_lines.len(), ours_lines.len(), theirs_lines.len()));
+        let base_chunk = &base_lines[i..next_i];
+        let ours_chunk = &ours_lines[j..next_j];
+        let theirs_chunk = &theirs_lines[k..next_k];
+
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            output.extend(theirs_chunk.iter().map(|line| line.to_string()));
+        } else if theirs_chunk == base_chunk {
+            output.extend(ours_chunk.iter().map(|line| line.to_string()));
+        } else {
+            conflicts += 1;
+            output.push(format!("<<<<<<< {}\n", ours_label));
+            output.extend(marked_chunk(ours_chunk));
+            output.push("=======\n".to_string());
+            output.extend(marked_chunk(theirs_chunk));
+            output.push(format!(">>>>>>> {}\n", theirs_label));
+        }
+        i = next_i;
+        j = next_j;
+        k = next_k;
+    }
+
+    MergedText { text: output.concat(), conflicts }
+}
+
+// the lines of a side of a conflict, ending with a line end so that the next marker starts a line
+fn marked_chunk(chunk: &[&str]) -> Vec<String> {
+    let mut lines: Vec<String> = chunk.iter().map(|line| line.to_string()).collect();
+    if let Some(last) = lines.last_mut().filter(|last| !last.ends_with('\n')) {
+        last.push('\n');
+    }
+    lines
+}
+
+// for every base line, the line of `other` it is kept as, None when it was changed or removed
+fn matched_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    for edit in myers_diff(base, other) {
+        if let Edit::Equal(i, j) = edit {
+            matches[i] = Some(j);
+        }
+    }
+    matches
+}
+
+fn read_content(path: &str, file: Option<&FileEntry>) -> Result<Vec<u8>, String> {
+    match file {
+        Some(file) => objects::read_object(path, &file.blob),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn files_of(path: &str, tree_id: Option<&str>) -> Result<BTreeMap<String, FileEntry>, String> {
+    match tree_id {
+        Some(tree_id) => tree::flatten(path, tree_id),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+// result of merging two trees file by file
+pub struct MergedTree {
+    pub tree: String,                        // merged files, the conflicted ones as they are on our side
+    pub conflicts: BTreeMap<String, Vec<u8>>, // path -> content with conflict markers, our side for binary files
+}
+
+// merge_trees: three-way merge of the files of two trees against the tree of their base
+pub fn merge_trees(path: &str, base: Option<&str>, ours: Option<&str>, theirs: Option<&str>, ours_label: &str, theirs_label: &str) -> Result<MergedTree, String> {
+    let base_files = files_of(path, base)?;
+    let ours_files = files_of(path, ours)?;
+    let theirs_files = files_of(path, theirs)?;
+    let mut names: Vec<&String> = base_files.keys().chain(ours_files.keys()).chain(theirs_files.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let mut merged_files = BTreeMap::new();
+    let mut conflicts = BTreeMap::new();
+    for name in names {
+        let (b, o, t) = (base_files.get(name), ours_files.get(name), theirs_files.get(name));
+        let merged = if o == t || t == b {
+            o.cloned()
+        } else if o == b {
+            t.cloned()
+        } else if let (Some(ours_file), Some(theirs_file)) = (o, t) {
+            let (base_content, ours_content, theirs_content) = (read_content(path, b)?, read_content(path, o)?, read_content(path, t)?);
+            if [&base_content, &ours_content, &theirs_content].iter().any(|content| is_binary(content)) {
+                // binary files cannot be merged line by line, our side stays until the user picks one
+                conflicts.insert(name.clone(), ours_content);
+                merged_files.insert(name.clone(), ours_file.clone());
+                continue;
+            }
+            let text = |content: &[u8]| String::from_utf8_lossy(content).to_string();
+            let result = merge3(&text(&base_content), &text(&ours_content), &text(&theirs_content), ours_label, theirs_label);
+            let blob = objects::write_object(path, result.text.as_bytes())?;
+            let mode = if Some(ours_file.mode) != b.map(|file| file.mode) { ours_file.mode } else { theirs_file.mode };
+            if result.conflicts > 0 {
+                conflicts.insert(name.clone(), result.text.into_bytes());
+                o.cloned()
+            } else {
+                Some(FileEntry { mode, blob })
+            }
+        } else {
+            // changed on one side, removed on the other: keep the changed file and let the user decide
+            conflicts.insert(name.clone(), read_content(path, o.or(t))?);
+            o.or(t).cloned()
+        };
+        if let Some(file) = merged {
+            merged_files.insert(name.clone(), file);
+        }
+    }
+    Ok(MergedTree { tree: tree::build_tree(path, &merged_files)?, conflicts })
+}
+
+// write_merge: update the working files from our tree to the merged one, conflicted files get their markers
+pub fn write_merge(path: &str, ours: Option<&str>, merged: &MergedTree) -> Result<(), String> {
+    switch_tree(path, ours, Some(&merged.tree))?;
+    for (name, content) in &merged.conflicts {
+        fs::write(format!("{}/{}", path, name), content).map_err(|err| format!("{}: {}", name, err))?;
+    }
+    Ok(())
+}
+
+// merge: merge the source branch or revision into the target, which must be checked out
+pub fn merge(path: &str, source: &str, target: &str, author: &str) -> Result<String, String> {
+    let head_path = layout::head_path(path);
+    let mut head_file = open_head(&head_path);
+    let ours = head_file.get_revision_id().to_string();
+    if target != head_file.get_branch() && target != ours {
+        return Err(format!("{} is not checked out, check it out before merging into it", target));
+    }
+    if fs::metadata(layout::merge_head_path(path)).is_ok() {
+        return Err("A merge is already in progress, resolve the conflicts and commit first".to_string());
+    }
+    let status = collect_status(path)?;
+    if !status.staged.is_empty() || !status.modified.is_empty() || !status.deleted.is_empty() {
+        return Err("Your local changes would be overwritten by merge, commit them first".to_string());
+    }
+
+    let (theirs, _) = resolve_revision(path, source)?;
+    let base = if ours.is_empty() { None } else { merge_base(path, &ours, &theirs) };
+    if base.as_deref() == Some(theirs.as_str()) {
+        return Ok(format!("Already up to date with {}", source));
+    }
+
+    // fast-forward: the target has nothing the source does not have
+    if ours.is_empty() || base.as_deref() == Some(ours.as_str()) {
+        let (suc_msg, err_msg) = switch_tree(path, revision_tree(path, &ours).as_deref(), revision_tree(path, &theirs).as_deref())?;
+        refs::update_head(path, &mut head_file, &theirs)?;
+        return result_format(suc_msg, err_msg, &format!("Fast-forward to {}, updated files", theirs));
+    }
+
+    let base_tree = base.as_deref().and_then(|base_id| revision_tree(path, base_id));
+    let ours_tree = revision_tree(path, &ours);
+    let merged = merge_trees(path, base_tree.as_deref(), ours_tree.as_deref(), revision_tree(path, &theirs).as_deref(), target, source)?;
+    write_merge(path, ours_tree.as_deref(), &merged)?;
+    let merged_tree = merged.tree;
+
+    if merged.conflicts.is_empty() {
+        let related_files = tree::diff_trees(path, ours_tree.as_deref(), Some(&merged_tree))?
+            .into_iter()
+            .map(|(relative, _, _)| relative)
+            .collect();
+        let rev = Revision::new(
+            path.to_string(),
+            Some(ours),
+            Some(theirs),
+            author.to_string(),
+            format!("Merge {} into {}", source, target),
+            related_files,
+            merged_tree,
+        );
+        rev.save_revision();
+        refs::update_head(path, &mut head_file, &rev.get_id())?;
+        return Ok(format!("Merged {} into {}: revision {}", source, target, rev.get_id()));
+    }
+
+    // stop with the conflicts in the working tree: the merged files are staged, the conflicted
+    // ones have to be fixed and added before the commit that concludes the merge
+    let mut staging_area = open_staging_area(path)?;
+    for (relative, _, _) in tree::diff_trees(path, ours_tree.as_deref(), Some(&merged_tree))? {
+        staging_area.stage_file(path, &format!("{}/{}", path, relative))?;
+    }
+    staging_area.save(path);
+    fs::write(layout::merge_head_path(path), &theirs).map_err(|err| err.to_string())?;
+
+    let names: Vec<String> = merged.conflicts.into_keys().collect();
+    Err(format!("Automatic merge failed, fix the conflicts in: {}\nThen add the files and commit the result.", names.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> (String, usize) {
+        let merged = merge3(base, ours, theirs, "ours", "theirs");
+        (merged.text, merged.conflicts)
+    }
+
+    #[test]
+    fn test_merge3_clean() {
+        // changes in different places are both taken
+        assert_eq!(merge("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n"), ("A\nb\nC\n".to_string(), 0));
+        // the same change on both sides is taken once
+        assert_eq!(merge("a\nb\n", "a\nx\n", "a\nx\n"), ("a\nx\n".to_string(), 0));
+        // one side unchanged
+        assert_eq!(merge("a\n", "a\n", "a\nb\n"), ("a\nb\n".to_string(), 0));
+        assert_eq!(merge("", "", ""), (String::new(), 0));
+        // a file added on both sides with the same content
+        assert_eq!(merge("", "new\n", "new\n"), ("new\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_merge3_conflict() {
+        let (text, conflicts) = merge("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+        assert_eq!(conflicts, 1);
+        assert_eq!(text, "a\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nc\n");
+        // both sides add different lines at the end
+        let (text, conflicts) = merge("a\n", "a\nx\n", "a\ny\n");
+        assert_eq!(conflicts, 1);
+        assert_eq!(text, "a\n<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\n");
+    }
+
+    #[test]
+    fn test_merge3_line_ends() {
+        // a change of the line ends alone is kept
+        assert_eq!(merge("a\nb\nc\n", "a\r\nb\nc\n", "a\nb\nC\n"), ("a\r\nb\nC\n".to_string(), 0));
+        // no newline is added that neither side had
+        assert_eq!(merge("a\nb", "A\nb", "a\nb"), ("A\nb".to_string(), 0));
+        assert_eq!(merge("a\nb", "a\nb", "a\nb\n"), ("a\nb\n".to_string(), 0));
+        // CRLF on one side and new lines on the other touch the same lines: a conflict, not a silent loss
+        let (text, conflicts) = merge("a\nb", "a\r\nb\n", "a\nb\nside");
+        assert_eq!(conflicts, 1);
+        assert!(text.contains("a\r\nb\n=======\n"));
+        // a side without a final newline still leaves the markers on their own lines
+        assert!(text.ends_with("side\n>>>>>>> theirs\n"));
+    }
+}
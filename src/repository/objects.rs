@@ -0,0 +1,54 @@
+use crate::repository::layout;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+// The object store keeps every committed file content once, under .mdv/objects/<SHA-256 of the content>
+
+// hash_object: SHA-256 of the given content as a lowercase hex string
+pub fn hash_object(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .fold(String::new(), |acc, byte| acc + &format!("{:02x}", byte))
+}
+
+pub fn object_path(path: &str, id: &str) -> String {
+    format!("{}/{}", layout::objects_dir(path), id)
+}
+
+pub fn object_exists(path: &str, id: &str) -> bool {
+    Path::new(&object_path(path, id)).is_file()
+}
+
+// write_blob: store the content of a file and return its blob id, identical contents are only stored once
+pub fn write_blob(path: &str, file_path: &str) -> Result<String, String> {
+    let content = fs::read(file_path).map_err(|err| format!("{}: {}", file_path, err))?;
+    write_object(path, &content)
+}
+
+pub fn write_object(path: &str, content: &[u8]) -> Result<String, String> {
+    let id = hash_object(content);
+    if !object_exists(path, &id) {
+        let _ = fs::create_dir_all(layout::objects_dir(path));
+        fs::write(object_path(path, &id), content).map_err(|err| err.to_string())?;
+    }
+    Ok(id)
+}
+
+pub fn read_object(path: &str, id: &str) -> Result<Vec<u8>, String> {
+    fs::read(object_path(path, id)).map_err(|_| format!("Missing object {}", id))
+}
+
+// restore_blob: write the content of a blob back to a working file, creating parent folders as needed
+pub fn restore_blob(path: &str, id: &str, file_path: &str) -> Result<(), String> {
+    let content = read_object(path, id)?;
+    if let Some(parent) = Path::new(file_path).parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    fs::write(file_path, content).map_err(|err| format!("{}: {}", file_path, err))
+}
+
+// hash_file: blob id a working file would get, None if it cannot be read
+pub fn hash_file(file_path: &str) -> Option<String> {
+    fs::read(file_path).ok().map(|content| hash_object(&content))
+}
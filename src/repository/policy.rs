@@ -0,0 +1,166 @@
+use crate::repository::config;
+use crate::repository::ignore;
+use regex::Regex;
+
+// The file name policy decides which new files a repository accepts, from the repository config:
+//
+// file.allow            patterns a path must match, every path when not set
+// file.deny             patterns no path may match
+// file.maxpathlength    longest path in characters, relative to the repository
+// file.reservednames    names no part of a path may have, case-insensitive (for example CON, PRN, NUL)
+//
+// Lists are separated by commas, patterns use the .mdvignore syntax: without a slash they match a name
+// at any depth, with one the whole path, and a pattern matching a folder covers everything inside it.
+// By default every file is allowed.
+
+#[derive(Debug, Default)]
+pub struct FilePolicy {
+    allow: Vec<(String, Regex)>,
+    deny: Vec<(String, Regex)>,
+    max_path_length: Option<usize>,
+    reserved_names: Vec<String>,
+}
+
+fn list(path: &str, key: &str) -> Vec<String> {
+    config::get(path, key).unwrap_or_default()
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn patterns(path: &str, key: &str) -> Vec<(String, Regex)> {
+    list(path, key).into_iter()
+        .filter_map(|pattern| ignore::pattern_regex(pattern.trim_end_matches('/')).map(|regex| (pattern, regex)))
+        .collect()
+}
+
+// the first pattern matching the path or one of its folders
+fn matching<'a>(patterns: &'a [(String, Regex)], relative: &str) -> Option<&'a str> {
+    let parts: Vec<&str> = relative.split('/').collect();
+    (1..=parts.len())
+        .map(|end| parts[..end].join("/"))
+        .find_map(|prefix| patterns.iter().find(|(_, regex)| regex.is_match(&prefix)))
+        .map(|(pattern, _)| pattern.as_str())
+}
+
+impl FilePolicy {
+    pub fn load(path: &str) -> FilePolicy {
+        FilePolicy {
+            allow: patterns(path, "file.allow"),
+            deny: patterns(path, "file.deny"),
+            max_path_length: config::get(path, "file.maxpathlength").and_then(|length| length.parse().ok()),
+            reserved_names: list(path, "file.reservednames").into_iter().map(|name| name.to_lowercase()).collect(),
+        }
+    }
+
+    // check: whether a new file may be added, with the rule it breaks
+    pub fn check(&self, relative: &str) -> Result<(), String> {
+        if let Some(max) = self.max_path_length {
+            if relative.chars().count() > max {
+                return Err(format!("The path is longer than file.maxpathlength ({} characters)", max));
+            }
+        }
+        for part in relative.split('/') {
+            // reserved device names also count with an extension, as in NUL.txt
+            let stem = part.split('.').next().unwrap_or(part).to_lowercase();
+            if self.reserved_names.contains(&part.to_lowercase()) || self.reserved_names.contains(&stem) {
+                return Err(format!("{} is a reserved name (file.reservednames)", part));
+            }
+        }
+        if let Some(pattern) = matching(&self.deny, relative) {
+            return Err(format!("The path is denied by file.deny pattern {}", pattern));
+        }
+        if !self.allow.is_empty() && matching(&self.allow, relative).is_none() {
+            let allowed: Vec<&str> = self.allow.iter().map(|(pattern, _)| pattern.as_str()).collect();
+            return Err(format!("The path matches none of the file.allow patterns: {}", allowed.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiled(patterns: &[&str]) -> Vec<(String, Regex)> {
+        patterns.iter()
+            .map(|pattern| (pattern.to_string(), ignore::pattern_regex(pattern.trim_end_matches('/')).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_allows_everything() {
+        let policy = FilePolicy::default();
+        for relative in ["a.txt", "NUL.txt", "deep/folder/file.bin", &"x".repeat(500)] {
+            assert_eq!(policy.check(relative), Ok(()), "{}", relative);
+        }
+    }
+
+    #[test]
+    fn test_deny() {
+        let policy = FilePolicy { deny: compiled(&["*.exe", "secrets/", "build/out"]), ..FilePolicy::default() };
+        assert_eq!(policy.check("tool.exe"), Err("The path is denied by file.deny pattern *.exe".to_string()));
+        assert!(policy.check("bin/tool.exe").is_err());
+        // a folder pattern covers everything inside it, at any depth without a slash in front
+        assert_eq!(policy.check("secrets/key.pem"), Err("The path is denied by file.deny pattern secrets/".to_string()));
+        assert!(policy.check("app/secrets/key.pem").is_err());
+        // with a slash inside, the pattern is anchored at the root
+        assert!(policy.check("build/out/a.o").is_err());
+        assert_eq!(policy.check("src/build/out/a.o"), Ok(()));
+        assert_eq!(policy.check("tool.exe.txt"), Ok(()));
+        assert_eq!(policy.check("secrets.txt"), Ok(()));
+    }
+
+    #[test]
+    fn test_allow() {
+        let policy = FilePolicy { allow: compiled(&["*.rs", "docs/"]), deny: compiled(&["docs/private"]), ..FilePolicy::default() };
+        assert_eq!(policy.check("src/main.rs"), Ok(()));
+        assert_eq!(policy.check("docs/guide.md"), Ok(()));
+        assert_eq!(policy.check("notes.txt"), Err("The path matches none of the file.allow patterns: *.rs, docs/".to_string()));
+        // deny wins over allow
+        assert_eq!(policy.check("docs/private/plan.md"), Err("The path is denied by file.deny pattern docs/private".to_string()));
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        let policy = FilePolicy { reserved_names: vec!["con".to_string(), "nul".to_string(), "com1".to_string()], ..FilePolicy::default() };
+        assert_eq!(policy.check("NUL"), Err("NUL is a reserved name (file.reservednames)".to_string()));
+        assert_eq!(policy.check("NUL.txt"), Err("NUL.txt is a reserved name (file.reservednames)".to_string()));
+        assert_eq!(policy.check("nul.tar.gz"), Err("nul.tar.gz is a reserved name (file.reservednames)".to_string()));
+        // any part of the path counts, folders included
+        assert_eq!(policy.check("Con/readme.md"), Err("Con is a reserved name (file.reservednames)".to_string()));
+        assert!(policy.check("src/com1.log").is_err());
+        for relative in ["null.txt", "console.log", "nul_file", "a.con", "com10"] {
+            assert_eq!(policy.check(relative), Ok(()), "{}", relative);
+        }
+    }
+
+    #[test]
+    fn test_max_path_length() {
+        let policy = FilePolicy { max_path_length: Some(10), ..FilePolicy::default() };
+        assert_eq!(policy.check("abcd/f.txt"), Ok(()));
+        assert_eq!(policy.check("abcd/fg.txt"), Err("The path is longer than file.maxpathlength (10 characters)".to_string()));
+        // the length is counted in characters, not bytes
+        assert_eq!(policy.check("ääää/ö.txt"), Ok(()));
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("mdv-policy-load-{}", std::process::id())).to_string_lossy().to_string();
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(crate::repository::layout::mdv_dir(&path)).unwrap();
+        std::fs::write(
+            crate::repository::layout::config_path(&path),
+            "[file]\n\tdeny = *.exe, ,tmp/\n\treservednames = CON,Nul\n\tmaxpathlength = 20\n",
+        ).unwrap();
+        let policy = FilePolicy::load(&path);
+        let deny: Vec<&str> = policy.deny.iter().map(|(pattern, _)| pattern.as_str()).collect();
+        assert_eq!(deny, vec!["*.exe", "tmp/"]);
+        assert!(policy.allow.is_empty());
+        assert_eq!(policy.reserved_names, vec!["con", "nul"]);
+        assert_eq!(policy.max_path_length, Some(20));
+        assert!(policy.check("nul.txt").is_err());
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
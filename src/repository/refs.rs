@@ -0,0 +1,274 @@
+use crate::machine::file_management::list_files_recursive;
+use crate::repository::layout;
+use crate::repository::merge::ancestors;
+use crate::repository::revision::{open_head, open_revision, resolve_revision, short_id, Head};
+use crate::repository::tag;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Branches are files under .mdv/refs/heads/<branch> holding the id of their tip revision.
+// head.json names the current branch, and every revision recorded on it moves the branch file along.
+
+// is_valid_ref_name: branch and tag names, `/` separated parts made of letters, digits, `-`, `_` and `.`,
+// with no part starting with `.`, no leading `-`, no `..` and no `.lock` suffix
+pub fn is_valid_ref_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && !name.ends_with(".lock")
+        && name.split('/').all(|part| !part.is_empty() && !part.starts_with('.'))
+        && !name.contains("..")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c))
+}
+
+pub fn branch_exists(path: &str, name: &str) -> bool {
+    is_valid_ref_name(name) && Path::new(&layout::branch_path(path, name)).is_file()
+}
+
+// read_branch: tip revision of a branch, None if there is no such branch
+pub fn read_branch(path: &str, name: &str) -> Option<String> {
+    if !is_valid_ref_name(name) {
+        return None;
+    }
+    fs::read_to_string(layout::branch_path(path, name))
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+pub fn write_branch(path: &str, name: &str, rev_id: &str) -> Result<(), String> {
+    write_ref(&layout::branch_path(path, name), rev_id)
+}
+
+// write_ref: store an id in a ref file, creating the folders of a `group/name` ref
+pub fn write_ref(ref_path: &str, id: &str) -> Result<(), String> {
+    if let Some(parent) = Path::new(ref_path).parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    fs::write(ref_path, id).map_err(|err| format!("{}: {}", ref_path, err))
+}
+
+// remove_ref: delete a ref file and the folders of a `group/name` ref left empty, up to `refs_root`
+pub fn remove_ref(ref_path: &str, refs_root: &str) -> Result<(), String> {
+    fs::remove_file(ref_path).map_err(|err| format!("{}: {}", ref_path, err))?;
+    let refs_root = Path::new(refs_root).to_path_buf();
+    let mut parent = Path::new(ref_path).parent().map(Path::to_path_buf);
+    while let Some(dir) = parent {
+        if dir == refs_root || fs::remove_dir(&dir).is_err() {
+            break;
+        }
+        parent = dir.parent().map(Path::to_path_buf);
+    }
+    Ok(())
+}
+
+// list_branches: every branch with its tip revision, sorted by name
+pub fn list_branches(path: &str) -> Vec<(String, String)> {
+    let heads_dir = layout::branches_dir(path);
+    list_files_recursive(&heads_dir, &[])
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|file_path| {
+            let name = file_path.strip_prefix(&format!("{}/", heads_dir))?.to_string();
+            let tip = read_branch(path, &name)?;
+            Some((name, tip))
+        })
+        .collect()
+}
+
+// update_head: point the head at a new revision, moving the current branch along with it
+pub fn update_head(path: &str, head_file: &mut Head, rev_id: &str) -> Result<(), String> {
+    head_file.set_revision_id(rev_id.to_string());
+    head_file.save_head(&layout::head_path(path));
+    if head_file.get_branch().is_empty() {
+        Ok(())
+    } else {
+        write_branch(path, head_file.get_branch(), rev_id)
+    }
+}
+
+// labels: names pointing at each revision, `HEAD -> <branch>` first for the current branch and tags last
+pub fn labels(path: &str) -> HashMap<String, Vec<String>> {
+    let head_file = open_head(&layout::head_path(path));
+    let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+    if head_file.get_branch().is_empty() && !head_file.get_revision_id().is_empty() {
+        labels.entry(head_file.get_revision_id().to_string()).or_default().push("HEAD".to_string());
+    }
+    for (name, tip) in list_branches(path) {
+        let names = labels.entry(tip).or_default();
+        if name == head_file.get_branch() {
+            names.insert(0, format!("HEAD -> {}", name));
+        } else {
+            names.push(name);
+        }
+    }
+    for (name, target) in tag::list_tags(path) {
+        labels.entry(target.revision).or_default().push(format!("tag: {}", name));
+    }
+    labels
+}
+
+// heads: list the branches with their tip revision, the current one marked with `*`
+pub fn heads(path: &str) -> Result<String, String> {
+    let head_file = open_head(&layout::head_path(path));
+    let current = head_file.get_branch();
+    let mut lines = Vec::new();
+
+    if current.is_empty() {
+        lines.push(format!("* (detached at {})", short_id(head_file.get_revision_id())));
+    } else if read_branch(path, current).is_none() {
+        lines.push(format!("* {} (no revisions yet)", current));
+    }
+    for (name, tip) in list_branches(path) {
+        let marker = if name == current { "*" } else { " " };
+        let message = open_revision(&layout::revision_path(path, &tip)).get_message().lines().next().unwrap_or("").to_string();
+        lines.push(format!("{} {} {} {}", marker, name, short_id(&tip), message));
+    }
+    Ok(lines.join("\n"))
+}
+
+// create_branch: a new branch at the given revision, or at the head revision
+pub fn create_branch(path: &str, name: &str, start: Option<&str>) -> Result<String, String> {
+    if !is_valid_ref_name(name) {
+        return Err(format!("{} is not a valid branch name", name));
+    }
+    if branch_exists(path, name) {
+        return Err(format!("A branch named {} already exists", name));
+    }
+    let rev_id = match start {
+        Some(start) => resolve_revision(path, start)?.0,
+        None => {
+            let head_file = open_head(&layout::head_path(path));
+            if head_file.get_revision_id().is_empty() {
+                return Err("Nothing committed yet, a branch needs a revision to point to".to_string());
+            }
+            head_file.get_revision_id().to_string()
+        },
+    };
+    write_branch(path, name, &rev_id)?;
+    Ok(format!("Created branch {} at {}", name, short_id(&rev_id)))
+}
+
+// delete_branch: remove a branch, unless its tip is not part of the head revision's history (or force is set)
+pub fn delete_branch(path: &str, name: &str, force: bool) -> Result<String, String> {
+    let head_file = open_head(&layout::head_path(path));
+    let tip = read_branch(path, name).ok_or(format!("Branch {} not found", name))?;
+    if head_file.get_branch() == name {
+        return Err(format!("Cannot delete branch {}, it is checked out", name));
+    }
+    let head_id = head_file.get_revision_id();
+    if !force && (head_id.is_empty() || !ancestors(path, head_id).contains(&tip)) {
+        return Err(format!("Branch {} is not fully merged into the head revision, use branch -D {} to delete it anyway", name, name));
+    }
+    remove_ref(&layout::branch_path(path, name), &layout::branches_dir(path))?;
+    Ok(format!("Deleted branch {} (was {})", name, short_id(&tip)))
+}
+
+// rename_branch: move a branch to a new name, the head follows when it is the current branch
+pub fn rename_branch(path: &str, old_name: &str, new_name: &str) -> Result<String, String> {
+    let head_path = layout::head_path(path);
+    let mut head_file = open_head(&head_path);
+    if !is_valid_ref_name(new_name) {
+        return Err(format!("{} is not a valid branch name", new_name));
+    }
+    if branch_exists(path, new_name) {
+        return Err(format!("A branch named {} already exists", new_name));
+    }
+    let is_current = head_file.get_branch() == old_name;
+    match read_branch(path, old_name) {
+        Some(tip) => {
+            write_branch(path, new_name, &tip)?;
+            remove_ref(&layout::branch_path(path, old_name), &layout::branches_dir(path))?;
+        },
+        // the current branch may have no revision yet, only the head knows about it
+        None if is_current => {},
+        None => return Err(format!("Branch {} not found", old_name)),
+    }
+    if is_current {
+        head_file.set_branch(new_name.to_string());
+        head_file.save_head(&head_path);
+    }
+    Ok(format!("Renamed branch {} to {}", old_name, new_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::revision::{add, checkout, commit};
+
+    fn repository(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mdv-refs-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        layout::init_repository(&path, false, false, false).unwrap();
+        path
+    }
+
+    fn commit_file(path: &str, name: &str, content: &str) -> String {
+        fs::write(format!("{}/{}", path, name), content).unwrap();
+        add(path, vec![name]).unwrap();
+        commit(path, vec![], name, "tester", false).unwrap();
+        open_head(&layout::head_path(path)).get_revision_id().to_string()
+    }
+
+    fn current_branch(path: &str) -> String {
+        open_head(&layout::head_path(path)).get_branch().to_string()
+    }
+
+    #[test]
+    fn test_delete_branch() {
+        let path = repository("delete");
+        let c1 = commit_file(&path, "a.txt", "1");
+        let main = current_branch(&path);
+        create_branch(&path, "merged", None).unwrap();
+        create_branch(&path, "work", None).unwrap();
+        checkout(&path, "work").unwrap();
+        let w1 = commit_file(&path, "b.txt", "work");
+        checkout(&path, &main).unwrap();
+
+        assert_eq!(delete_branch(&path, &main, false), Err(format!("Cannot delete branch {}, it is checked out", main)));
+        assert_eq!(delete_branch(&path, "missing", false), Err("Branch missing not found".to_string()));
+        // a tip in the history of the head revision is merged work
+        assert_eq!(delete_branch(&path, "merged", false), Ok(format!("Deleted branch merged (was {})", short_id(&c1))));
+        // the revision only the branch holds would be lost
+        assert_eq!(
+            delete_branch(&path, "work", false),
+            Err("Branch work is not fully merged into the head revision, use branch -D work to delete it anyway".to_string()),
+        );
+        assert_eq!(read_branch(&path, "work"), Some(w1.clone()));
+        assert_eq!(delete_branch(&path, "work", true), Ok(format!("Deleted branch work (was {})", short_id(&w1))));
+        assert_eq!(list_branches(&path), vec![(main, c1)]);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rename_branch() {
+        let path = repository("rename");
+        // the current branch before its first revision only lives in the head
+        let main = current_branch(&path);
+        assert!(rename_branch(&path, &main, "first").is_ok());
+        assert_eq!(current_branch(&path), "first");
+        let c1 = commit_file(&path, "a.txt", "1");
+        assert_eq!(read_branch(&path, "first"), Some(c1.clone()));
+
+        create_branch(&path, "other", None).unwrap();
+        assert_eq!(rename_branch(&path, "first", "other"), Err("A branch named other already exists".to_string()));
+        assert_eq!(rename_branch(&path, "first", "bad..name"), Err("bad..name is not a valid branch name".to_string()));
+        assert_eq!(rename_branch(&path, "missing", "new"), Err("Branch missing not found".to_string()));
+
+        // renaming the current branch moves the head along, new revisions go to the new name
+        assert_eq!(rename_branch(&path, "first", "group/renamed"), Ok("Renamed branch first to group/renamed".to_string()));
+        assert_eq!(current_branch(&path), "group/renamed");
+        assert_eq!(read_branch(&path, "first"), None);
+        let c2 = commit_file(&path, "a.txt", "2");
+        assert_eq!(read_branch(&path, "group/renamed"), Some(c2));
+        assert_eq!(read_branch(&path, "other"), Some(c1));
+
+        // a branch that is not checked out leaves the head alone, empty group folders go
+        assert!(rename_branch(&path, "other", "plain").is_ok());
+        assert!(rename_branch(&path, "group/renamed", "back").is_ok());
+        assert_eq!(current_branch(&path), "back");
+        assert!(!Path::new(&format!("{}/group", layout::branches_dir(&path))).exists());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use crate::repository::config::{self, ConfigLayer};
+use crate::repository::diff;
+use crate::repository::ignore;
+use crate::repository::layout;
+use crate::repository::log::{self, LogFilter};
+use crate::repository::merge;
+use crate::repository::refs;
+use crate::repository::revision;
+use crate::repository::stash;
+use crate::repository::status;
+use crate::repository::tag;
+
+// Repository is the entry point of the Repository Module, every command from the Interaction Module goes through it.
+pub struct Repository {
+    path: String,
+}
+
+impl Repository {
+    pub fn new(path: &str) -> Repository {
+        Repository {
+            path: path.to_string(),
+        }
+    }
+
+    pub fn init(&self, bare: bool, force: bool, wipe: bool) -> Result<String, String> {
+        layout::init_repository(&self.path, bare, force, wipe)
+    }
+
+    // commands that read or write working files cannot run in a bare repository
+    fn require_work_tree(&self) -> Result<(), String> {
+        if layout::is_bare(&self.path) {
+            Err(format!("{} is a bare repository, this command needs a working tree", self.path))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn needs_upgrade(&self) -> bool {
+        layout::has_legacy_data(&self.path)
+    }
+
+    pub fn upgrade(&self) -> Result<String, String> {
+        layout::upgrade_legacy(&self.path)
+    }
+
+    // config commands also work outside a repository, they then only see the user, system and environment layers
+    pub fn config_get(path: Option<&str>, key: &str) -> Result<String, String> {
+        config::show(path, key)
+    }
+
+    pub fn config_set(path: Option<&str>, layer: ConfigLayer, key: &str, value: &str) -> Result<String, String> {
+        config::set(path, layer, key, value)
+    }
+
+    pub fn config_unset(path: Option<&str>, layer: ConfigLayer, key: &str) -> Result<String, String> {
+        config::unset(path, layer, key)
+    }
+
+    pub fn config_list(path: Option<&str>) -> Result<String, String> {
+        config::list(path)
+    }
+
+    pub fn create(&self, file_names: Vec<&str>) -> Result<String, String> {
+        self.require_work_tree()?;
+        revision::create(&self.path, file_names)
+    }
+
+    pub fn add(&self, paths: Vec<&str>) -> Result<String, String> {
+        self.require_work_tree()?;
+        revision::add(&self.path, paths)
+    }
+
+    pub fn remove(&self, paths: Vec<&str>) -> Result<String, String> {
+        self.require_work_tree()?;
+        revision::remove(&self.path, paths)
+    }
+
+    pub fn cat(&self, revision: &str, file_name: &str) -> Result<Vec<u8>, String> {
+        revision::cat(&self.path, revision, file_name)
+    }
+
+    pub fn checkout(&self, branch_or_commit: &str) -> Result<String, String> {
+        self.require_work_tree()?;
+        revision::checkout(&self.path, branch_or_commit)
+    }
+
+    // commit: the author is the configured identity unless one is given
+    pub fn commit(&self, message: &str, author: Option<&str>, only: Vec<&str>, allow_empty: bool) -> Result<String, String> {
+        self.require_work_tree()?;
+        let author = author.map(|author| author.to_string()).unwrap_or_else(|| config::author(&self.path));
+        revision::commit(&self.path, only, message, &author, allow_empty)
+    }
+
+    pub fn merge(&self, source: &str, target: &str) -> Result<String, String> {
+        self.require_work_tree()?;
+        merge::merge(&self.path, source, target, &config::author(&self.path))
+    }
+
+    pub fn stash_push(&self, message: Option<&str>) -> Result<String, String> {
+        self.require_work_tree()?;
+        stash::push(&self.path, message, &config::author(&self.path))
+    }
+
+    pub fn stash_list(&self) -> Result<String, String> {
+        stash::list(&self.path)
+    }
+
+    pub fn stash_apply(&self, index: usize, drop: bool) -> Result<String, String> {
+        self.require_work_tree()?;
+        stash::apply(&self.path, index, drop)
+    }
+
+    pub fn status(&self) -> Result<String, String> {
+        self.require_work_tree()?;
+        status::status(&self.path)
+    }
+
+    pub fn check_ignore(&self, paths: Vec<&str>) -> Result<String, String> {
+        self.require_work_tree()?;
+        ignore::check_ignore(&self.path, &paths)
+    }
+
+    // diff: without -U the context comes from diff.context
+    pub fn diff(&self, from: &str, to: &str, context: Option<usize>) -> Result<String, String> {
+        if (from.starts_with("--") || to.starts_with("--")) && layout::is_bare(&self.path) {
+            return Err(format!("{} is a bare repository, only revisions can be compared", self.path));
+        }
+        let context = context.unwrap_or_else(|| config::diff_context(&self.path, diff::DEFAULT_CONTEXT));
+        diff::diff(&self.path, from, to, context)
+    }
+
+    pub fn log(&self, filter: &LogFilter, graph: bool) -> Result<String, String> {
+        log::log(&self.path, filter, graph)
+    }
+
+    pub fn heads(&self) -> Result<String, String> {
+        refs::heads(&self.path)
+    }
+
+    pub fn create_branch(&self, name: &str, start: Option<&str>) -> Result<String, String> {
+        refs::create_branch(&self.path, name, start)
+    }
+
+    pub fn delete_branch(&self, name: &str, force: bool) -> Result<String, String> {
+        refs::delete_branch(&self.path, name, force)
+    }
+
+    pub fn rename_branch(&self, old_name: &str, new_name: &str) -> Result<String, String> {
+        refs::rename_branch(&self.path, old_name, new_name)
+    }
+
+    pub fn tags(&self) -> Result<String, String> {
+        tag::tags(&self.path)
+    }
+
+    pub fn create_tag(&self, name: &str, revision: Option<&str>, message: Option<&str>) -> Result<String, String> {
+        tag::create_tag(&self.path, name, revision, message, &config::author(&self.path))
+    }
+
+    pub fn delete_tag(&self, name: &str) -> Result<String, String> {
+        tag::delete_tag(&self.path, name)
+    }
+}
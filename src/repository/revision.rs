@@ -1,7 +1,16 @@
 use serde::{Serialize, Deserialize};
 use chrono::{Local};
-use sha2::{Digest, Sha256};
-use crate::staging::StagingArea;
+use crate::repository::staging::StagingArea;
+use crate::repository::ignore::{self, IgnoreRules};
+use crate::repository::layout;
+use crate::repository::objects;
+use crate::repository::policy::FilePolicy;
+use crate::repository::refs;
+use crate::repository::revspec;
+use crate::repository::tree::{self, FileEntry};
+use crate::machine::file_management::{create_file, read_file, write_file};
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::Path;
 
 #[derive(Serialize,Deserialize,Debug)]
@@ -13,6 +22,8 @@ struct RevisionInfo{
     date: String,
     commit_msg: String,
     related_files: Vec<String>,
+    #[serde(default)]
+    tree: String, // SHA-256 of the root tree
 }
 
 #[derive(Debug)]
@@ -35,6 +46,14 @@ impl Head {
         }
     }
 
+    pub fn get_revision_id(&self) -> &str {
+        &self.revision_id
+    }
+
+    pub fn get_branch(&self) -> &str {
+        &self.branchname
+    }
+
     pub fn set_revision_id(&mut self, id: String) {
         self.revision_id = id;
     }
@@ -45,7 +64,7 @@ impl Head {
 
     pub fn save_head(&self, path: &str) {
         let data = serde_json::to_string(&self).unwrap();
-        let _ = writeFile(&path, &data);
+        let _ = write_file(path, data.as_bytes());
     }
 }
 
@@ -57,38 +76,91 @@ impl Revision{
         author: String,
         commit_msg: String,
         related_files:Vec<String>,
+        tree: String,
+    ) -> Revision {
+        let current_time = Local::now().to_rfc3339();
+        Revision::new_at(path, main_parent, branch_parent, author, commit_msg, related_files, tree, current_time)
+    }
+
+    // new_at: a revision with a given date, used when converting history from elsewhere
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_at(
+        path: String,
+        main_parent: Option<String>,
+        branch_parent: Option<String>,
+        author: String,
+        commit_msg: String,
+        related_files:Vec<String>,
+        tree: String,
+        current_time: String,
     ) -> Revision {
-        let current_time = Local::now().to_string();
-        let commit_id = Revision::generate_id(&author,&commit_msg,&current_time,related_files.clone());
-        let store_path = format!("{}/{}/{}", path,".mdv/rev",commit_id);
+        let mut info = RevisionInfo {
+            main_parent,
+            branch_parent,
+            id: String::new(),
+            author,
+            date: current_time,
+            commit_msg,
+            related_files,
+            tree,
+        };
+        info.id = Revision::generate_id(&info);
+        let store_path = layout::revision_dir(&path, &info.id);
         let _ = fs::create_dir_all(&store_path);
         Revision {
             path: store_path,
-            info: RevisionInfo {
-                main_parent,
-                branch_parent,
-                id: commit_id,
-                author,
-                date: current_time,
-                commit_msg,
-                related_files,
-            },
+            info,
         }
     }
 
-    fn generate_id(author: &String,commit_msg: &String,date: &String,related_files:Vec<String>) -> String{
-        Sha256::digest(
-            format!("{}{}{}{:?}", author,commit_msg,date,related_files).as_bytes()
+    // generate_id: hash of the canonical form of a revision, so the id covers the whole content
+    // (through the root tree) and the whole history (through the parent ids)
+    fn generate_id(info: &RevisionInfo) -> String{
+        objects::hash_object(Revision::canonical_form(info).as_bytes())
+    }
+
+    fn canonical_form(info: &RevisionInfo) -> String{
+        format!(
+            "tree {}\nmain_parent {}\nbranch_parent {}\nauthor {}\ndate {}\n\n{}",
+            info.tree,
+            info.main_parent.as_deref().unwrap_or(""),
+            info.branch_parent.as_deref().unwrap_or(""),
+            info.author,
+            info.date,
+            info.commit_msg,
         )
-        .iter()
-        .fold(String::new(), |acc, byte| acc + &format!("{:02x}", byte))
     }
 
-    fn get_id(&self) -> String{
+    // verify: the stored id must match the content of the revision
+    pub fn verify(&self) -> Result<(), String>{
+        if Revision::generate_id(&self.info) == self.info.id {
+            Ok(())
+        } else {
+            Err(format!("Revision {} is corrupted: its id does not match its content", self.info.id))
+        }
+    }
+
+    pub fn get_id(&self) -> String{
         self.info.id.clone()
     }
 
-    fn get_parent_ids(&self) -> (Option<&String>, Option<&String>) {
+    pub fn get_author(&self) -> &str {
+        &self.info.author
+    }
+
+    pub fn get_related_files(&self) -> &[String] {
+        &self.info.related_files
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.info.commit_msg
+    }
+
+    pub fn get_date(&self) -> &str {
+        &self.info.date
+    }
+
+    pub fn get_parent_ids(&self) -> (Option<&String>, Option<&String>) {
         (
             self.info.main_parent.as_ref(),
             self.info.branch_parent.as_ref(),
@@ -98,20 +170,16 @@ impl Revision{
     pub fn save_revision(&self){
         let data = serde_json::to_string(&self.info).unwrap();
         let store_path = format!("{}/{}",&self.path, "revision.json");
-        let _ =writeFile(&store_path,&data);
+        let _ =write_file(&store_path,data.as_bytes());
     }
 }
 
-fn folder_exists(path: &str) -> bool {
-    fs::metadata(path).map(|metadata| metadata.is_dir()).unwrap_or(false)
-}
-
 fn open_json<T>(path: &str) -> T
 where
     T: serde::de::DeserializeOwned,
 {
-    let json_data = readFile(&path).unwrap();
-    serde_json::from_str(&json_data).expect("Failed to deserialize JSON")
+    let json_data = read_file(path).unwrap();
+    serde_json::from_slice(&json_data).expect("Failed to deserialize JSON")
 }
 
 pub fn open_head(path: &str) -> Head {
@@ -123,13 +191,23 @@ pub fn open_revision(path: &str) -> Revision {
     Revision { path: path.to_string(), info: json_data }
 }
 
-pub fn open_staging_area(path: &str) -> StagingArea {
-    let tracked_files: Vec<String> = open_json(path);
-    StagingArea { tracked_files }
+// open_staging_area: read the index of a repository, or the JSON staging area next to it in older repositories
+pub fn open_staging_area(path: &str) -> Result<StagingArea, String> {
+    let staging_path = layout::staging_path(path);
+    match fs::read(&staging_path) {
+        Ok(data) => StagingArea::from_index(&data, path).map_err(|err| format!("{}: {}", staging_path, err)),
+        Err(_) => {
+            let legacy_path = Path::new(&staging_path).with_file_name(layout::LEGACY_STAGING_FILE).to_string_lossy().to_string();
+            match read_file(&legacy_path) {
+                Ok(data) => StagingArea::from_json(&data).map_err(|err| format!("{}: {}", legacy_path, err)),
+                Err(_) => Ok(StagingArea::new()),
+            }
+        },
+    }
 }
 
 
-fn FileOperation<F>(path: &str, filenames: Vec<&str>, mut operation: F) -> (Vec<String>, Vec<String>) 
+fn file_operation<F>(path: &str, filenames: Vec<&str>, mut operation: F) -> (Vec<String>, Vec<String>) 
     where F: FnMut(&str) -> Result<(), String>,
 {
     filenames
@@ -147,162 +225,498 @@ fn FileOperation<F>(path: &str, filenames: Vec<&str>, mut operation: F) -> (Vec<
         )
 }
 
-pub fn copy_file(from: &str, to: &str, name:&str){
-    let to_path = format!("{}/{}", to, name);
-    let _ = createFile(&to_path);
-    fs::copy(&from, &to_path).expect("failed to copy");
+// expand_pathspecs: the files named by each path of a command, out of the given candidates
+// (relative paths). `.` names every candidate, a folder the candidates inside it and a glob
+// (*, ?, [...], ** across folders) the candidates whose whole path matches it.
+// Any other path is kept as it is, a folder or glob without any candidate is an error.
+fn expand_pathspecs(path: &str, specs: Vec<&str>, candidates: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut files: Vec<String> = Vec::new();
+    let mut errors = Vec::new();
+    for spec in specs {
+        let relative = spec.trim_start_matches("./").trim_end_matches('/');
+        // a path may not climb out of the working tree, whatever it would match
+        if !relative.is_empty() && relative != "." {
+            if let Err(err) = tree::check_path(relative) {
+                errors.push(format!("Failed: {} -> {}", spec, err));
+                continue;
+            }
+        }
+        let matched: Vec<String> = if relative.is_empty() || relative == "." {
+            candidates.to_vec()
+        } else if relative.contains(['*', '?', '[']) {
+            let regex = ignore::glob_regex(relative);
+            candidates.iter().filter(|candidate| regex.as_ref().is_some_and(|regex| regex.is_match(candidate))).cloned().collect()
+        } else if candidates.iter().any(|candidate| candidate == relative) || !Path::new(&format!("{}/{}", path, relative)).is_dir() {
+            vec![relative.to_string()]
+        } else {
+            let prefix = format!("{}/", relative);
+            candidates.iter().filter(|candidate| candidate.starts_with(&prefix)).cloned().collect()
+        };
+        if matched.is_empty() {
+            errors.push(format!("Failed: {} -> Didn't match any files.", spec));
+        }
+        for file in matched {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    (files, errors)
+}
+
+// path of a working file relative to the repository root, as recorded in revisions
+pub fn relative_path(path: &str, file_path: &str) -> String {
+    file_path.strip_prefix(&format!("{}/", path)).unwrap_or(file_path).to_string()
+}
+
+// short form of a revision id for messages and listings
+pub fn short_id(rev_id: &str) -> String {
+    rev_id.chars().take(8).collect()
 }
 
-fn ResultFormat(success: Vec<String>, errors: Vec<String>, success_message: &str) -> Result<String, String> {
+// result_format: the files that succeeded, then one line per failure. Any failure makes it an error.
+pub fn result_format(success: Vec<String>, errors: Vec<String>, success_message: &str) -> Result<String, String> {
     if errors.is_empty() {
         Ok(format!("{}: {}", success_message, success.join(", ")))
-    } else {
+    } else if success.is_empty() {
         Err(errors.join("\n"))
+    } else {
+        Err(format!("{}: {}\n{}", success_message, success.join(", "), errors.join("\n")))
     }
 }
 
 // create: create new files
 pub fn create(path: &str, filenames: Vec<&str>) -> Result<String, String>{
-    let (suc_msg, err_msg) = FileOperation(path, filenames, |file_path| {
-        createFile(file_path).map_err(|err| err.to_string())
+    let policy = FilePolicy::load(path);
+    let (suc_msg, err_msg) = file_operation(path, filenames, |file_path| {
+        let relative = relative_path(path, file_path);
+        tree::check_path(&relative)?;
+        policy.check(&relative)?;
+        if let Some(parent) = Path::new(file_path).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        create_file(file_path).map_err(|err| err.to_string())
     });
-    ResultFormat(suc_msg, err_msg, "Successfully created files")
+    result_format(suc_msg, err_msg, "Successfully created files")
 }
 
-// remove: remove specific files from tracking list
+// remove: remove specific files from tracking list, folders and globs name the staged files they match
 pub fn remove(path: &str, filenames: Vec<&str>) -> Result<String, String>{
-    let staging_path = format!("{}/{}/{}", path, ".mdv", "staging_area.json");
-    let mut staging_area = open_staging_area(&staging_path);
+    let mut staging_area = open_staging_area(path)?;
 
-    let (suc_msg, err_msg)= FileOperation(path, filenames, |file_path| {
+    let staged: Vec<String> = staging_area.get_tracked_files().iter().map(|file_path| relative_path(path, file_path)).collect();
+    let (filenames, mut err_msg) = expand_pathspecs(path, filenames, &staged);
+    let (suc_msg, errors)= file_operation(path, filenames.iter().map(|name| name.as_str()).collect(), |file_path| {
         if staging_area.get_tracked_files().contains(&file_path.to_string()) {
-            staging_area.remove_staging_file(&file_path);
+            staging_area.remove_staging_file(file_path);
             Ok(())
         } else {
-            Err(format!("Didn't match any files."))
+            Err("Didn't match any files.".to_string())
         }
     });
+    err_msg.extend(errors);
 
-    staging_area.save_to_json(&staging_path);
-    ResultFormat(suc_msg, err_msg, "Successfully removed files")
+    staging_area.save(path);
+    result_format(suc_msg, err_msg, "Successfully removed files")
 }
 
-// add: add specific files that you want to track
+// add: add specific files that you want to track. Folders and globs name the new and changed files they
+// match that are not ignored, including tracked files deleted from disk, `.` names all of them.
 pub fn add(path: &str, filenames: Vec<&str>) -> Result<String, String>{
-    let staging_path = format!("{}/{}/{}", path, ".mdv", "staging_area.json");
-    let mut staging_area = open_staging_area(&staging_path);
-
-    let (suc_msg, err_msg)= FileOperation(path, filenames, |file_path| {
-        staging_area.push_staging_file(&file_path);Ok(())
+    let mut staging_area = open_staging_area(path)?;
+
+    // ignored files and files against the file name policy can only be added once they are tracked
+    let ignore_rules = IgnoreRules::load(path);
+    let policy = FilePolicy::load(path);
+    let tracked = match revision_tree(path, open_head(&layout::head_path(path)).get_revision_id()) {
+        Some(tree_id) => tree::flatten(path, &tree_id)?,
+        None => BTreeMap::new(),
+    };
+    // unchanged tracked files are left out, there is nothing to stage for them. Only the files the paths
+    // name are checked, and the stat cache saves hashing the ones that were not touched.
+    let mut named: Vec<String> = ignore::working_files(path).into_iter().chain(tracked.keys().cloned()).collect();
+    named.sort();
+    named.dedup();
+    let (named, _) = expand_pathspecs(path, filenames.clone(), &named);
+    let mut candidates: Vec<String> = named.into_iter()
+        .filter(|relative| tracked.get(relative).is_none_or(|file| !is_clean(&staging_area, path, relative, file)))
+        .collect();
+    for file_path in staging_area.get_tracked_files() {
+        candidates.push(relative_path(path, file_path));
+    }
+    candidates.sort();
+    candidates.dedup();
+    let (filenames, mut err_msg) = expand_pathspecs(path, filenames, &candidates);
+
+    let (suc_msg, errors)= file_operation(path, filenames.iter().map(|name| name.as_str()).collect(), |file_path| {
+        let relative = relative_path(path, file_path);
+        let is_tracked = tracked.contains_key(&relative) || staging_area.get_tracked_files().contains(&file_path.to_string());
+        if !is_tracked {
+            if !Path::new(file_path).exists() {
+                return Err("Didn't match any files.".to_string());
+            }
+            if let Some(rule) = ignore_rules.check(&relative, Path::new(file_path).is_dir()).filter(|rule| !rule.negated) {
+                return Err(format!("The path is ignored by {}:{}: {}", rule.source, rule.line, rule.pattern));
+            }
+            policy.check(&relative)?;
+        }
+        // a file back to its committed content has nothing to stage, an earlier staged change is dropped
+        let same_as_head = tracked.get(&relative).is_some_and(|file| is_clean(&staging_area, path, &relative, file));
+        if same_as_head {
+            staging_area.remove_staging_file(file_path);
+            return Ok(());
+        }
+        staging_area.stage_file(path, file_path)
     });
+    err_msg.extend(errors);
 
-    staging_area.save_to_json(&staging_path);
-    ResultFormat(suc_msg, err_msg, "Successfully added files")
+    staging_area.save(path);
+    result_format(suc_msg, err_msg, "Successfully added files")
 }
 
+// is_clean: whether a working file still has the content and mode it has in a tree
+fn is_clean(staging_area: &StagingArea, path: &str, relative: &str, file: &FileEntry) -> bool {
+    let file_path = format!("{}/{}", path, relative);
+    Path::new(&file_path).is_file() && tree::file_mode(&file_path) == file.mode
+        && (staging_area.is_cached_clean(&file_path, &file.blob) || objects::hash_file(&file_path).as_ref() == Some(&file.blob))
+}
 
-// // commit changes and create a new revision
-pub fn commit(path: &str, filenames: Vec<&str>, msg: &str, author: &str) -> Result<String, String>{
-    let staging_path = format!("{}/{}/{}", path, ".mdv", "staging_area.json");
-    let mut staging_area = open_staging_area(&staging_path);
-    let head_path = format!("{}/{}/{}", path, ".mdv", "head.json");
+// commit: create a new revision from everything staged, or with `only` from just the given paths as
+// they are on disk now, the rest stays staged. A revision that changes nothing needs `allow_empty`,
+// unless it concludes a merge.
+pub fn commit(path: &str, only: Vec<&str>, msg: &str, author: &str, allow_empty: bool) -> Result<String, String>{
+    if !only.is_empty() {
+        add(path, only.clone())?;
+    }
+    let mut staging_area = open_staging_area(path)?;
+    let head_path = layout::head_path(path);
     let mut head_file = open_head(&head_path);
-    
-    let mut rev = Revision::new(
+
+    let staged: Vec<String> = staging_area.get_tracked_files().iter().map(|file_path| relative_path(path, file_path)).collect();
+    let related_files = if only.is_empty() {
+        staged
+    } else {
+        let (selected, errors) = expand_pathspecs(path, only, &staged);
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        selected
+    };
+
+    // the new tree is the tree of the parent revision with the committed files replaced by their staged content
+    let parent_tree = revision_tree(path, &head_file.revision_id);
+    let head_files = match &parent_tree {
+        Some(tree_id) => tree::flatten(path, tree_id)?,
+        None => BTreeMap::new(),
+    };
+    let mut files = head_files.clone();
+    for relative in &related_files {
+        let file_path = format!("{}/{}", path, relative);
+        match staging_area.staged_file(path, &file_path)? {
+            Some(file) => { files.insert(relative.clone(), file); },
+            // a staged deletion removes the file from the tree
+            None => { files.remove(relative); },
+        }
+    }
+    let tree_id = tree::build_tree(path, &files)?;
+
+    // a merge that stopped on conflicts is concluded by this commit
+    let merge_head = fs::read_to_string(layout::merge_head_path(path)).ok().map(|id| id.trim().to_string());
+    let unchanged = related_files.is_empty() || parent_tree.as_ref() == Some(&tree_id);
+    if unchanged && merge_head.is_none() && !allow_empty {
+        // a staged snapshot identical to the head revision is no change to keep either,
+        // anything else (a path staged without a snapshot included) stays staged
+        for relative in &related_files {
+            let file_path = format!("{}/{}", path, relative);
+            let same_as_head = match staging_area.snapshots.get(&file_path) {
+                Some(Some(entry)) => head_files.get(relative) == Some(&entry.file_entry()),
+                Some(None) => !head_files.contains_key(relative),
+                None => false,
+            };
+            if same_as_head {
+                staging_area.remove_staging_file(&file_path);
+            }
+        }
+        staging_area.save(path);
+        return Err("Nothing to commit, add the changes first or use --allow-empty".to_string());
+    }
+
+    let rev = Revision::new(
         path.to_string(),
-        Some(head_file.revision_id.clone()),
-        None,
+        Some(head_file.revision_id.clone()).filter(|id| !id.is_empty()),
+        merge_head,
         author.to_string(),
         msg.to_string(),
-        Vec::new(),
+        related_files.clone(),
+        tree_id,
     );
-    
-    let (suc_msg, err_msg) = FileOperation(path, filenames, |file_path| {
-        if staging_area.get_tracked_files().contains(&file_path.to_string()) {
-            let path = Path::new(file_path);
-            let filename = path.file_name().unwrap().to_str().unwrap();
-            let _ = copy_file(&file_path, &rev.path, filename);
-            rev.info.related_files.push(file_path.to_string());
-            staging_area.remove_staging_file(&file_path);
-            Ok(())
-        } else {
-            Err(format!("Files {} are not staged", file_path))
-        }
-    });
-
     rev.save_revision();
-    staging_area.save_to_json(&staging_path);
-    head_file.set_revision_id(rev.get_id());
-    head_file.save_head(&head_path);
+    let _ = fs::remove_file(layout::merge_head_path(path));
+    for relative in &related_files {
+        staging_area.remove_staging_file(&format!("{}/{}", path, relative));
+    }
+    staging_area.save(path);
+    refs::update_head(path, &mut head_file, &rev.get_id())?;
 
-    ResultFormat(suc_msg, err_msg, "Successfully committed files")
+    if related_files.is_empty() {
+        Ok(format!("Committed revision {} without changes", short_id(&rev.get_id())))
+    } else {
+        Ok(format!("Committed revision {} with files: {}", short_id(&rev.get_id()), related_files.join(", ")))
+    }
 }
 
-// head: show the current head
-pub fn head(path: &str) -> Result<String, String>{
-    let head_path = format!("{}/{}/{}", path, ".mdv", "head.json");
-    let head_file = open_head(&head_path);
+// cat: the content of a file of a given revision, byte for byte
+pub fn cat(path: &str, commit_id: &str,filename: &str) -> Result<Vec<u8>, String>{
+    tree::check_path(filename).map_err(|err| format!("{}: {}", filename, err))?;
+    let (rev_id, _) = resolve_revision(path, commit_id)?;
+    let rev = open_revision(&layout::revision_path(path, &rev_id));
 
-    if head_file.revision_id.is_empty(){
-        Err("Nothing commited yet, the head is empty".to_string())
-    } else{
-        let rev_path = format!("{}/{}/{}/{}",path, ".mdv/rev", head_file.revision_id.as_str(),"revision.json");
-        let rev = open_revision(&rev_path);
-        Ok(format!("Commit: {}\nHead -> {}\nAuthor: {}\nDate: {}", head_file.revision_id, head_file.branchname, rev.info.author, rev.info.date))
+    match tree::lookup(path, &rev.info.tree, filename)? {
+        Some(file) => objects::read_object(path, &file.blob),
+        None => Err(format!("File {} is not recorded in revision {}", filename, commit_id)),
     }
-} 
+}
 
-// cat: inspect a file of a given revision
-pub fn cat(path: &str, commit_id: &str,filename: &str) -> Result<String, String>{
-    let rev_path = format!("{}/{}/{}/{}",path, ".mdv/rev", commit_id, filename);
+// resolve a branch name or any revision specifier (see revspec) to a revision id, and whether it named a branch
+pub fn resolve_revision(path: &str, name: &str) -> Result<(String, bool), String>{
+    if let Some(tip) = refs::read_branch(path, name) {
+        return Ok((tip, true));
+    }
 
-    match readFile(&rev_path){
-        Ok(content) => Ok(format!("{}: {}", filename, content)),
-        Err(err) => Err(format!("Invalid Revision")),
+    let head_file = open_head(&layout::head_path(path));
+    if !head_file.branchname.is_empty() && head_file.branchname == name {
+        return Err(format!("Branch {} does not have any revision yet", name));
     }
+
+    revspec::resolve(path, name).map(|id| (id, false))
 }
 
-// branch: create a new branch and switch to the branch
-pub fn branch(path: &str, branchname: &str) -> Result<String, String>{
-    let head_path = format!("{}/{}/{}", path, ".mdv", "head.json");
-    let mut head_file = open_head(&head_path);
-    let rev_path = format!("{}/{}", path, ".mdv/rev");
-    // 未commit的报错; 已存在的branchname报错;
-    if (!folder_exists(&rev_path)){
-        Err(format!("Not a valid object name:{}", head_file.branchname))
-    } else{
-        head_file.set_branch(branchname.to_string());
-        head_file.save_head(&head_path);
-        println!("{:?}",head_file);
-        Ok(format!("switched to branch:{}",branchname))
+// root tree of a revision, None when there is no revision yet
+pub fn revision_tree(path: &str, rev_id: &str) -> Option<String>{
+    let rev_path = layout::revision_path(path, rev_id);
+    if rev_id.is_empty() || fs::metadata(&rev_path).is_err() {
+        return None;
     }
+    Some(open_revision(&rev_path).info.tree).filter(|tree_id| !tree_id.is_empty())
 }
 
-
-// //check out a specific revision
+// check out a specific revision
 pub fn checkout(path: &str, commit_id: &str) -> Result<String, String>{
+    let staging_area = open_staging_area(path)?;
+    let head_path = layout::head_path(path);
+    let mut head_file = open_head(&head_path);
 
+    let (target_id, is_branch) = resolve_revision(path, commit_id)?;
+    if !staging_area.staging_is_empty() {
+        let staged: Vec<String> = staging_area.get_tracked_files().iter().map(|file_path| relative_path(path, file_path)).collect();
+        return Err(format!("Your staged changes would be overwritten by checkout: {}\nPlease commit them before you switch.", staged.join(", ")));
+    }
+
+    open_revision(&layout::revision_path(path, &target_id)).verify()?;
+
+    let head_tree = revision_tree(path, &head_file.revision_id);
+    let target_tree = revision_tree(path, &target_id);
+    let (suc_msg, err_msg) = switch_tree(path, head_tree.as_deref(), target_tree.as_deref())?;
 
+    head_file.set_revision_id(target_id.clone());
+    head_file.set_branch(if is_branch { commit_id.to_string() } else { String::new() });
+    head_file.save_head(&head_path);
+
+    result_format(suc_msg, err_msg, &format!("Checked out revision {}, updated files", target_id))
 }
 
-// use std::fs::{self, copy};
-// use std::io::{Error, Write, Read};
-// pub fn readFile(path: &str) -> Result<String, Error> {
-//     // Read the contents of the file at the specified path
-//     let mut content = String::new();
-//     fs::File::open(path)?.read_to_string(&mut content)?;
-//     Ok(content)
-// }
-
-// pub fn createFile(path: &str) -> Result<(), Error> {
-//     // Create an empty file at the specified path
-//     fs::File::create(path)?;
-//     Ok(())
-// }
-
-// pub fn writeFile(path: &str, content: &str) -> Result<(), Error> {
-//     // Write the provided content to the file at the specified path
-//     let mut file = fs::File::create(path)?;
-//     file.write_all(content.as_bytes())?;
-//     Ok(())
-// }
\ No newline at end of file
+// switch_tree: update the working files from one tree to another, returns the updated and the failed files.
+// Nothing is written when a file differs from both trees, it holds local changes that would be lost.
+pub fn switch_tree(path: &str, from_tree: Option<&str>, to_tree: Option<&str>) -> Result<(Vec<String>, Vec<String>), String>{
+    let changes = tree::diff_trees(path, from_tree, to_tree)?;
+
+    let dirty_files: Vec<String> = changes.iter()
+        .filter(|(relative, old, new)| {
+            let current = objects::hash_file(&format!("{}/{}", path, relative));
+            current.as_ref() != old.as_ref().map(|file| &file.blob) && current.as_ref() != new.as_ref().map(|file| &file.blob)
+        })
+        .map(|(relative, _, _)| relative.clone())
+        .collect();
+    if !dirty_files.is_empty() {
+        return Err(format!("Your local changes to the following files would be overwritten: {}\nPlease commit them before you switch.", dirty_files.join(", ")));
+    }
+
+    Ok(changes.iter().fold(
+        (Vec::new(), Vec::new()),
+        |(mut success, mut errors), (relative, _, new)| {
+            let result = match new {
+                Some(file) => tree::restore_file(path, relative, file),
+                None if objects::hash_file(&format!("{}/{}", path, relative)).is_some() => tree::remove_file(path, relative),
+                None => Ok(()),
+            };
+            match result {
+                Ok(()) => success.push(relative.clone()),
+                Err(err) => errors.push(format!("Failed: {} -> {}", relative, err)),
+            }
+            (success, errors)
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::staging::IndexEntry;
+
+    fn repository(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mdv-revision-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        layout::init_repository(&path, false, false, false).unwrap();
+        path
+    }
+
+    fn write(path: &str, name: &str, content: &str) {
+        let file_path = format!("{}/{}", path, name);
+        fs::create_dir_all(Path::new(&file_path).parent().unwrap()).unwrap();
+        fs::write(file_path, content).unwrap();
+    }
+
+    fn commit_files(path: &str, files: &[(&str, &str)]) -> String {
+        for (name, content) in files {
+            write(path, name, content);
+        }
+        add(path, files.iter().map(|(name, _)| *name).collect()).unwrap();
+        commit(path, vec![], "change", "tester", false).unwrap();
+        head(path).get_revision_id().to_string()
+    }
+
+    fn head(path: &str) -> Head {
+        open_head(&layout::head_path(path))
+    }
+
+    fn read(path: &str, name: &str) -> Option<String> {
+        fs::read_to_string(format!("{}/{}", path, name)).ok()
+    }
+
+    // main: a.txt "1", keep.txt / other: a.txt "2", keep.txt, dir/new.txt
+    fn branches(name: &str) -> (String, String, String) {
+        let path = repository(name);
+        let main_id = commit_files(&path, &[("a.txt", "1"), ("keep.txt", "keep")]);
+        let main = head(&path).get_branch().to_string();
+        refs::create_branch(&path, "other", None).unwrap();
+        checkout(&path, "other").unwrap();
+        let other_id = commit_files(&path, &[("a.txt", "2"), ("dir/new.txt", "new")]);
+        checkout(&path, &main).unwrap();
+        (path, main_id, other_id)
+    }
+
+    #[test]
+    fn test_checkout_switches_branches() {
+        let (path, main_id, other_id) = branches("switch");
+        let main = head(&path).get_branch().to_string();
+        assert_eq!(head(&path).get_revision_id(), main_id);
+        // files missing from the target tree are removed, with the folders they leave empty
+        assert_eq!(read(&path, "a.txt").as_deref(), Some("1"));
+        assert!(!Path::new(&format!("{}/dir", path)).exists());
+
+        assert!(checkout(&path, "other").is_ok());
+        assert_eq!((head(&path).get_revision_id(), head(&path).get_branch()), (other_id.as_str(), "other"));
+        assert_eq!(read(&path, "a.txt").as_deref(), Some("2"));
+        assert_eq!(read(&path, "dir/new.txt").as_deref(), Some("new"));
+        assert_eq!(read(&path, "keep.txt").as_deref(), Some("keep"));
+
+        assert!(checkout(&path, &main).is_ok());
+        assert_eq!(head(&path).get_branch(), main);
+        assert_eq!(read(&path, "dir/new.txt"), None);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_detached() {
+        let (path, main_id, other_id) = branches("detached");
+        assert!(checkout(&path, &other_id[..8]).is_ok());
+        assert_eq!((head(&path).get_revision_id(), head(&path).get_branch()), (other_id.as_str(), ""));
+        assert_eq!(read(&path, "a.txt").as_deref(), Some("2"));
+        // a revision is not a branch, even when a branch points to it
+        assert!(checkout(&path, &main_id).is_ok());
+        assert_eq!((head(&path).get_revision_id(), head(&path).get_branch()), (main_id.as_str(), ""));
+        assert_eq!(read(&path, "dir/new.txt"), None);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_keeps_local_changes() {
+        let (path, main_id, _) = branches("refuse");
+
+        // a changed tracked file the target replaces
+        write(&path, "a.txt", "local");
+        let result = checkout(&path, "other");
+        assert!(result.unwrap_err().starts_with("Your local changes to the following files would be overwritten: a.txt"));
+        assert_eq!(read(&path, "a.txt").as_deref(), Some("local"));
+        assert_eq!(head(&path).get_revision_id(), main_id);
+
+        // an untracked file where the target has a file
+        write(&path, "a.txt", "1");
+        write(&path, "dir/new.txt", "mine");
+        let result = checkout(&path, "other");
+        assert!(result.unwrap_err().starts_with("Your local changes to the following files would be overwritten: dir/new.txt"));
+        assert_eq!(read(&path, "dir/new.txt").as_deref(), Some("mine"));
+        assert_eq!(read(&path, "a.txt").as_deref(), Some("1"));
+
+        // the same content as the target is no loss, a change to a file both trees share is kept
+        write(&path, "dir/new.txt", "new");
+        write(&path, "keep.txt", "changed");
+        assert!(checkout(&path, "other").is_ok());
+        assert_eq!(read(&path, "keep.txt").as_deref(), Some("changed"));
+
+        // staged changes
+        write(&path, "staged.txt", "staged");
+        add(&path, vec!["staged.txt"]).unwrap();
+        assert!(checkout(&path, &main_id).unwrap_err().starts_with("Your staged changes would be overwritten by checkout: staged.txt"));
+        assert_eq!(head(&path).get_branch(), "other");
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_nothing_to_commit_keeps_real_changes() {
+        let path = repository("nothing");
+        commit_files(&path, &[("a.txt", "1")]);
+        let head_file = tree::lookup(&path, &revision_tree(&path, head(&path).get_revision_id()).unwrap(), "a.txt").unwrap().unwrap();
+
+        // a snapshot equal to HEAD, the deletion of a file HEAD does not have and a path staged
+        // without a snapshot, whose content is only known at commit time
+        let mut staging_area = open_staging_area(&path).unwrap();
+        staging_area.stage_snapshot(&format!("{}/a.txt", path), Some(IndexEntry::from_object(&path, &head_file)));
+        staging_area.stage_snapshot(&format!("{}/gone.txt", path), None);
+        staging_area.tracked_files.push(format!("{}/legacy.txt", path));
+        staging_area.save(&path);
+
+        let result = commit(&path, vec![], "nothing", "tester", false);
+        assert_eq!(result, Err("Nothing to commit, add the changes first or use --allow-empty".to_string()));
+        assert_eq!(open_staging_area(&path).unwrap().tracked_files, vec![format!("{}/legacy.txt", path)]);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_add_pathspecs() {
+        let path = repository("add");
+        commit_files(&path, &[("a.txt", "1"), ("dir/b.txt", "1"), ("dir/c.txt", "1")]);
+        write(&path, "dir/b.txt", "2");
+        write(&path, "dir/new.txt", "new");
+
+        // a folder names its new and changed files, not the unchanged ones
+        assert_eq!(add(&path, vec!["dir"]), Ok("Successfully added files: dir/b.txt, dir/new.txt".to_string()));
+        // a file back to its committed content is taken off the staging area
+        write(&path, "dir/b.txt", "1");
+        assert_eq!(add(&path, vec!["dir/b.txt"]), Ok("Successfully added files: dir/b.txt".to_string()));
+        assert_eq!(open_staging_area(&path).unwrap().tracked_files, vec![format!("{}/dir/new.txt", path)]);
+        assert_eq!(add(&path, vec!["dir/*.md"]), Err("Failed: dir/*.md -> Didn't match any files.".to_string()));
+        assert_eq!(add(&path, vec!["../outside.txt"]), Err("Failed: ../outside.txt -> Not a valid path inside the working tree".to_string()));
+
+        // a file the stat cache vouches for is not hashed again
+        let head_blob = tree::lookup(&path, &revision_tree(&path, head(&path).get_revision_id()).unwrap(), "a.txt").unwrap().unwrap().blob;
+        write(&path, "a.txt", "2");
+        let mut staging_area = open_staging_area(&path).unwrap();
+        let file_path = format!("{}/a.txt", path);
+        staging_area.stat_cache.insert(file_path.clone(), IndexEntry::with_stat(&file_path, &head_blob).unwrap());
+        staging_area.save(&path);
+        assert_eq!(add(&path, vec!["."]), Ok("Successfully added files: dir/new.txt".to_string()));
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
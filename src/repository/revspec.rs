@@ -0,0 +1,234 @@
+use crate::repository::layout;
+use crate::repository::log::parse_date;
+use crate::repository::merge::parents;
+use crate::repository::refs;
+use crate::repository::revision::{open_head, open_revision, short_id};
+use crate::repository::tag;
+use chrono::DateTime;
+use std::fs;
+
+// A revision specifier names a revision, then walks from it:
+//
+// HEAD, @            the head revision
+// <branch>, <tag>    the revision the ref points to
+// <hash prefix>      a revision whose id starts with at least MIN_PREFIX hex digits
+// <rev>~N            the N-th main parent going back (~ alone is ~1)
+// <rev>^N            the N-th parent of a revision, ^1 the main and ^2 the branch parent (^ alone is ^1, ^0 is the revision itself)
+// <rev>@{<date>}     the newest revision on the main-parent line that is not newer than the date:
+//                    YYYY-MM-DD (up to the end of that day, as log --until), yesterday, or <n>.<unit>.ago
+//                    with a unit from minutes to years
+//
+// Suffixes can be chained, for example main~2^2 or HEAD@{yesterday}~1.
+
+pub const MIN_PREFIX: usize = 4;
+
+// resolve: the revision id a specifier stands for
+pub fn resolve(path: &str, spec: &str) -> Result<String, String> {
+    let base_end = spec.find(['~', '^', '@']).unwrap_or(spec.len());
+    let (base, mut rest) = spec.split_at(base_end);
+
+    // a lone `@` before the suffixes stands for HEAD
+    let base = if base.is_empty() && rest.starts_with('@') && !rest.starts_with("@{") {
+        rest = &rest[1..];
+        "HEAD"
+    } else {
+        base
+    };
+    let mut id = match base {
+        "" if rest.starts_with("@{") => resolve_name(path, "HEAD")?,
+        "" => return Err(format!("Invalid revision {}, it has to start with a name, HEAD or a revision id", spec)),
+        _ => resolve_name(path, base)?,
+    };
+
+    while !rest.is_empty() {
+        if let Some(date_spec) = rest.strip_prefix("@{") {
+            let end = date_spec.find('}').ok_or(format!("Invalid revision {}, missing }}", spec))?;
+            id = as_of(path, &id, &date_spec[..end])?;
+            rest = &date_spec[end + 1..];
+            continue;
+        }
+
+        let operator = rest.chars().next().unwrap();
+        let digits: String = rest[1..].chars().take_while(|c| c.is_ascii_digit()).collect();
+        let count: usize = if digits.is_empty() { 1 } else { digits.parse().map_err(|_| format!("Invalid number in revision {}", spec))? };
+        rest = &rest[1 + digits.len()..];
+        id = match operator {
+            '~' => ancestor(path, &id, count, spec)?,
+            '^' => nth_parent(path, &id, count, spec)?,
+            _ => return Err(format!("Invalid revision {}, unexpected {}", spec, operator)),
+        };
+    }
+    Ok(id)
+}
+
+// resolve_name: HEAD, a branch, a tag, a full revision id or a unique prefix of one
+fn resolve_name(path: &str, name: &str) -> Result<String, String> {
+    if name == "HEAD" {
+        let head_file = open_head(&layout::head_path(path));
+        if head_file.get_revision_id().is_empty() {
+            return Err("Nothing committed yet, HEAD does not point to a revision".to_string());
+        }
+        return Ok(head_file.get_revision_id().to_string());
+    }
+    if let Some(tip) = refs::read_branch(path, name) {
+        return Ok(tip);
+    }
+    if let Some(target) = tag::read_tag(path, name) {
+        return Ok(target.revision);
+    }
+
+    if name.len() < MIN_PREFIX || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Not a valid branch name or revision: {}", name));
+    }
+    if fs::metadata(layout::revision_path(path, name)).is_ok() {
+        return Ok(name.to_string());
+    }
+    let prefix = name.to_lowercase();
+    let mut candidates: Vec<String> = fs::read_dir(layout::revisions_dir(path))
+        .map_err(|err| err.to_string())?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|id| id.starts_with(&prefix))
+        .collect();
+    candidates.sort();
+    match candidates.len() {
+        0 => Err(format!("Not a valid branch name or revision: {}", name)),
+        1 => Ok(candidates.remove(0)),
+        _ => {
+            let listing: Vec<String> = candidates.iter()
+                .map(|id| {
+                    let rev = open_revision(&layout::revision_path(path, id));
+                    format!("  {} {} {}", &id[..(prefix.len() + 4).min(id.len())], rev.get_date(), rev.get_message().lines().next().unwrap_or(""))
+                })
+                .collect();
+            Err(format!("Revision prefix {} is ambiguous, candidates are:\n{}", name, listing.join("\n")))
+        },
+    }
+}
+
+// ancestor: go back `count` main parents
+fn ancestor(path: &str, id: &str, count: usize, spec: &str) -> Result<String, String> {
+    let mut id = id.to_string();
+    for step in 0..count {
+        id = parents(path, &id).into_iter().next()
+            .ok_or(format!("Invalid revision {}: the history only goes back {} revisions", spec, step))?;
+    }
+    Ok(id)
+}
+
+// nth_parent: parent number `n`, 0 is the revision itself
+fn nth_parent(path: &str, id: &str, n: usize, spec: &str) -> Result<String, String> {
+    if n == 0 {
+        return Ok(id.to_string());
+    }
+    parents(path, id).into_iter().nth(n - 1)
+        .ok_or(format!("Invalid revision {}: revision {} has no parent number {}", spec, short_id(id), n))
+}
+
+// as_of: the newest revision on the main-parent line of `id` recorded at or before the date,
+// a day counts up to its end
+fn as_of(path: &str, id: &str, date_spec: &str) -> Result<String, String> {
+    let limit = parse_date(date_spec, true)?;
+    let mut current = Some(id.to_string());
+    while let Some(id) = current {
+        let rev = open_revision(&layout::revision_path(path, &id));
+        if DateTime::parse_from_rfc3339(rev.get_date()).is_ok_and(|date| date <= limit) {
+            return Ok(id);
+        }
+        current = parents(path, &id).into_iter().next();
+    }
+    Err(format!("No revision is as old as {}", date_spec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::revision::{add, checkout, commit};
+
+    // a repository with three revisions on the main line and a merged side branch:
+    // c1 - c2 - c3 - m
+    //   \-- s1 ----/
+    struct History {
+        path: String,
+        c1: String,
+        c2: String,
+        c3: String,
+        s1: String,
+        m: String,
+    }
+
+    fn head(path: &str) -> String {
+        open_head(&layout::head_path(path)).get_revision_id().to_string()
+    }
+
+    fn commit_file(path: &str, name: &str, content: &str) -> String {
+        fs::write(format!("{}/{}", path, name), content).unwrap();
+        add(path, vec![name]).unwrap();
+        commit(path, vec![], name, "tester", false).unwrap();
+        head(path)
+    }
+
+    fn history(name: &str) -> History {
+        let path = std::env::temp_dir().join(format!("mdv-revspec-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        layout::init_repository(&path, false, false, false).unwrap();
+        let c1 = commit_file(&path, "a.txt", "1");
+        refs::create_branch(&path, "side", None).unwrap();
+        let c2 = commit_file(&path, "a.txt", "2");
+        let c3 = commit_file(&path, "a.txt", "3");
+        let main = open_head(&layout::head_path(&path)).get_branch().to_string();
+        checkout(&path, "side").unwrap();
+        let s1 = commit_file(&path, "b.txt", "side");
+        checkout(&path, &main).unwrap();
+        crate::repository::merge::merge(&path, "side", &main, "tester").unwrap();
+        tag::create_tag(&path, "v1.0.0", Some(&c2), None, "tester").unwrap();
+        let m = head(&path);
+        History { path, c1, c2, c3, s1, m }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let h = history("resolve");
+        let resolve = |spec: &str| resolve(&h.path, spec);
+        assert_eq!(resolve("HEAD"), Ok(h.m.clone()));
+        assert_eq!(resolve("@"), Ok(h.m.clone()));
+        assert_eq!(resolve("side"), Ok(h.s1.clone()));
+        assert_eq!(resolve("v1.0.0"), Ok(h.c2.clone()));
+        assert_eq!(resolve(&h.c3[..8]), Ok(h.c3.clone()));
+        assert_eq!(resolve(&h.c3.to_uppercase()[..8]), Ok(h.c3.clone()));
+
+        // ancestry
+        assert_eq!(resolve("HEAD~"), Ok(h.c3.clone()));
+        assert_eq!(resolve("HEAD~3"), Ok(h.c1.clone()));
+        assert_eq!(resolve("@~2"), Ok(h.c2.clone()));
+        assert_eq!(resolve("HEAD^"), Ok(h.c3.clone()));
+        assert_eq!(resolve("HEAD^2"), Ok(h.s1.clone()));
+        assert_eq!(resolve("HEAD^0"), Ok(h.m.clone()));
+        assert_eq!(resolve("HEAD^2~1"), Ok(h.c1.clone()));
+        assert_eq!(resolve("v1.0.0~1"), Ok(h.c1.clone()));
+
+        // dates
+        assert_eq!(resolve("HEAD@{2999-01-01}"), Ok(h.m.clone()));
+        assert_eq!(resolve("@{2999-01-01}~1"), Ok(h.c3.clone()));
+        // a day includes the revisions made during it, as log --until <day> does
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(resolve(&format!("HEAD@{{{}}}", today)), Ok(h.m.clone()));
+        assert_eq!(resolve(&format!("side@{{{}}}", today)), Ok(h.s1.clone()));
+        fs::remove_dir_all(&h.path).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let h = history("errors");
+        let resolve = |spec: &str| resolve(&h.path, spec);
+        assert!(resolve("").is_err());
+        assert!(resolve("~1").is_err());
+        assert!(resolve("abc").is_err()); // shorter than MIN_PREFIX
+        assert!(resolve("zzzz").is_err());
+        assert!(resolve("HEAD~10").unwrap_err().contains("only goes back 3 revisions"));
+        assert!(resolve("HEAD^3").unwrap_err().contains("has no parent number 3"));
+        assert!(resolve("HEAD@{2000-01-01}").is_err());
+        assert!(resolve("HEAD@{2000-01-01").is_err());
+        fs::remove_dir_all(&h.path).unwrap();
+    }
+}
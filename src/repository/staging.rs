@@ -1,27 +1,284 @@
+use crate::repository::layout;
+use crate::repository::objects;
+use crate::repository::tree::{self, FileEntry};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// The staging area keeps the staged paths with a snapshot of each file taken at add time, so that
+// later edits are not committed by accident. A staged path without a snapshot (None) is a staged deletion.
+// It also caches the stat data of clean tracked files, so status only hashes files whose stat data changed.
+//
+// It is stored in .mdv/index, every number big-endian:
+//
+// "MDVI", version (u32), entry count (u32)
+// per entry, sorted by path:
+//   flags (u8): 1 staged, 2 staged deletion, 0 a cached clean file
+//   mode (u32), size (u64), mtime (i64), ctime (i64), times in nanoseconds since the epoch
+//   blob (32 bytes SHA-256, all zero for a path staged before snapshots existed, read from disk at commit)
+//   path length (u16), path (UTF-8, relative to the repository root; indexes written before held absolute paths)
+// SHA-256 of everything before it
+//
+// Staging areas written before the index existed are read from staging_area.json.
+
+pub const INDEX_VERSION: u32 = 1;
+const INDEX_MAGIC: &[u8; 4] = b"MDVI";
+const FLAG_STAGED: u8 = 1;
+const FLAG_DELETED: u8 = 2;
+
 pub struct StagingArea{
     pub tracked_files: Vec<String>,  //path
+    pub snapshots: BTreeMap<String, Option<IndexEntry>>, // path -> staged content
+    pub stat_cache: BTreeMap<String, IndexEntry>, // path -> clean tracked file
+}
+
+// the staged content of a file with the stat data of the file it was taken from
+#[derive(Deserialize,Debug,Clone,PartialEq)]
+pub struct IndexEntry {
+    pub mode: u32,
+    pub blob: String, // SHA-256
+    pub size: u64,
+    pub mtime: i64, // nanoseconds since the epoch, 0 when unknown
+    #[serde(default)]
+    pub ctime: i64,
+}
+
+impl IndexEntry {
+    // from_file: store the content of a file and record its stat data
+    pub fn from_file(path: &str, file_path: &str) -> Result<IndexEntry, String> {
+        let blob = objects::write_blob(path, file_path)?;
+        IndexEntry::with_stat(file_path, &blob).ok_or(format!("{}: cannot read the file", file_path))
+    }
+
+    // with_stat: an entry for a file whose content is already stored as `blob`
+    pub fn with_stat(file_path: &str, blob: &str) -> Option<IndexEntry> {
+        let metadata = fs::metadata(file_path).ok()?;
+        let (mtime, ctime) = stat_times(&metadata);
+        Some(IndexEntry { mode: tree::file_mode(file_path), blob: blob.to_string(), size: metadata.len(), mtime, ctime })
+    }
+
+    // from_object: a stored file without stat data, the next status check hashes the file on disk
+    pub fn from_object(path: &str, file: &FileEntry) -> IndexEntry {
+        let size = fs::metadata(objects::object_path(path, &file.blob)).map_or(0, |metadata| metadata.len());
+        IndexEntry { mode: file.mode, blob: file.blob.clone(), size, mtime: 0, ctime: 0 }
+    }
+
+    pub fn file_entry(&self) -> FileEntry {
+        FileEntry { mode: self.mode, blob: self.blob.clone() }
+    }
+
+    // stat_matches: whether the file looks untouched since the entry was recorded
+    pub fn stat_matches(&self, file_path: &str) -> bool {
+        match fs::metadata(file_path) {
+            Ok(metadata) => self.mtime != 0 && metadata.len() == self.size && stat_times(&metadata) == (self.mtime, self.ctime),
+            Err(_) => false,
+        }
+    }
+
+    // is_unchanged: whether a file still has the staged content, its stat data saves hashing it
+    pub fn is_unchanged(&self, file_path: &str) -> bool {
+        self.stat_matches(file_path) || objects::hash_file(file_path).is_some_and(|blob| blob == self.blob)
+    }
+}
+
+// stat_times: modification and status change times in nanoseconds since the epoch
+#[cfg(unix)]
+pub fn stat_times(metadata: &fs::Metadata) -> (i64, i64) {
+    use std::os::unix::fs::MetadataExt;
+    (modified_time(metadata), metadata.ctime() * 1_000_000_000 + metadata.ctime_nsec())
+}
+
+#[cfg(not(unix))]
+pub fn stat_times(metadata: &fs::Metadata) -> (i64, i64) {
+    (modified_time(metadata), 0)
+}
+
+pub fn modified_time(metadata: &fs::Metadata) -> i64 {
+    metadata.modified().ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as i64)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StagingInfo {
+    Snapshots { files: Vec<String>, snapshots: BTreeMap<String, Option<IndexEntry>> },
+    Paths(Vec<String>),
+}
+
+// reads the fields of an index one after the other
+struct IndexReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> IndexReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.position..self.position + length).ok_or("Index file is truncated")?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn number<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
 }
 
 impl StagingArea{
     pub fn new() -> Self{
         Self{
             tracked_files: Vec::new(),
+            snapshots: BTreeMap::new(),
+            stat_cache: BTreeMap::new(),
         }
     }
 
-    pub fn save_to_json(&self, file_path: &str){
-        let data = serde_json::to_string(&self.tracked_files).unwrap();
-        let _ =writeFile(&file_path,&data);
+    pub fn from_json(data: &[u8]) -> Result<Self, String> {
+        let info = serde_json::from_slice(data).map_err(|err| format!("Staging area is corrupt: {}", err))?;
+        let (tracked_files, snapshots) = match info {
+            StagingInfo::Snapshots { files, snapshots } => (files, snapshots),
+            StagingInfo::Paths(files) => (files, BTreeMap::new()),
+        };
+        Ok(Self { tracked_files, snapshots, stat_cache: BTreeMap::new() })
+    }
+
+    // from_index: read the binary index of the repository at `path`, checking its checksum first
+    pub fn from_index(data: &[u8], path: &str) -> Result<Self, String> {
+        if data.len() < 12 + 32 || &data[..4] != INDEX_MAGIC {
+            return Err("Not an index file".to_string());
+        }
+        let (content, checksum) = data.split_at(data.len() - 32);
+        if Sha256::digest(content).as_slice() != checksum {
+            return Err("Index file is corrupt, its checksum does not match".to_string());
+        }
+
+        let mut reader = IndexReader { data: content, position: 4 };
+        let version = u32::from_be_bytes(reader.number()?);
+        if version != INDEX_VERSION {
+            return Err(format!("Unsupported index version {}", version));
+        }
+        let count = u32::from_be_bytes(reader.number()?);
+        let mut staging_area = StagingArea::new();
+        for _ in 0..count {
+            let flags = reader.number::<1>()?[0];
+            let mode = u32::from_be_bytes(reader.number()?);
+            let size = u64::from_be_bytes(reader.number()?);
+            let mtime = i64::from_be_bytes(reader.number()?);
+            let ctime = i64::from_be_bytes(reader.number()?);
+            let blob_bytes = reader.take(32)?;
+            let blob = blob_bytes.iter().fold(String::new(), |acc, byte| acc + &format!("{:02x}", byte));
+            let path_length = u16::from_be_bytes(reader.number()?) as usize;
+            let stored_path = String::from_utf8(reader.take(path_length)?.to_vec()).map_err(|_| "Index file has an invalid path")?;
+            // the rest of the repository works with the full paths of the working files
+            let file_path = if Path::new(&stored_path).is_absolute() { stored_path } else { format!("{}/{}", path, stored_path) };
+
+            let entry = IndexEntry { mode, blob, size, mtime, ctime };
+            if flags & FLAG_DELETED != 0 {
+                staging_area.stage_snapshot(&file_path, None);
+            } else if flags & FLAG_STAGED != 0 {
+                staging_area.tracked_files.push(file_path.clone());
+                if blob_bytes.iter().any(|byte| *byte != 0) {
+                    staging_area.snapshots.insert(file_path, Some(entry));
+                }
+            } else {
+                staging_area.stat_cache.insert(file_path, entry);
+            }
+        }
+        Ok(staging_area)
+    }
+
+    // to_index: the binary index of the repository at `path`, entries sorted by their path from the root,
+    // so that the index stays valid when the repository is moved
+    pub fn to_index(&self, path: &str) -> Vec<u8> {
+        let root = format!("{}/", path);
+        let stored_path = |file_path: &String| file_path.strip_prefix(&root).unwrap_or(file_path).to_string();
+        let mut entries: BTreeMap<String, (u8, Option<&IndexEntry>)> = self.stat_cache.iter()
+            .map(|(file_path, entry)| (stored_path(file_path), (0, Some(entry))))
+            .collect();
+        for file_path in &self.tracked_files {
+            let entry = match self.snapshots.get(file_path) {
+                Some(None) => (FLAG_STAGED | FLAG_DELETED, None),
+                Some(Some(entry)) => (FLAG_STAGED, Some(entry)),
+                None => (FLAG_STAGED, None),
+            };
+            entries.insert(stored_path(file_path), entry);
+        }
+
+        let mut data = INDEX_MAGIC.to_vec();
+        data.extend(INDEX_VERSION.to_be_bytes());
+        data.extend((entries.len() as u32).to_be_bytes());
+        for (file_path, (flags, entry)) in entries {
+            let (mode, size, mtime, ctime) = entry.map_or((0, 0, 0, 0), |entry| (entry.mode, entry.size, entry.mtime, entry.ctime));
+            data.push(flags);
+            data.extend(mode.to_be_bytes());
+            data.extend(size.to_be_bytes());
+            data.extend(mtime.to_be_bytes());
+            data.extend(ctime.to_be_bytes());
+            let blob = entry.map(|entry| entry.blob.as_str()).unwrap_or("");
+            data.extend((0..32).map(|i| blob.get(2 * i..2 * i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()).unwrap_or(0)));
+            data.extend((file_path.len() as u16).to_be_bytes());
+            data.extend(file_path.as_bytes());
+        }
+        let checksum = Sha256::digest(&data);
+        data.extend(checksum);
+        data
+    }
+
+    // save: write the index of the repository at `path`, which replaces a JSON staging area
+    pub fn save(&self, path: &str){
+        let staging_path = layout::staging_path(path);
+        let _ = fs::write(&staging_path, self.to_index(path));
+        let _ = fs::remove_file(Path::new(&staging_path).with_file_name(layout::LEGACY_STAGING_FILE));
     }
 
     pub fn remove_staging_file(&mut self, file_path: &str) {
-        self.tracked_files.retain(|x| x!= file_path)
+        self.tracked_files.retain(|x| x!= file_path);
+        self.snapshots.remove(file_path);
+    }
+
+    // stage_file: stage a file with its current content, or its deletion when it is gone from disk
+    pub fn stage_file(&mut self, path: &str, file_path: &str) -> Result<(), String> {
+        let snapshot = if Path::new(file_path).is_file() {
+            Some(IndexEntry::from_file(path, file_path)?)
+        } else {
+            None
+        };
+        self.stage_snapshot(file_path, snapshot);
+        Ok(())
     }
 
-    pub fn push_staging_file(&mut self, file_path: &str) {
+    // stage_snapshot: stage content that is already in the object store
+    pub fn stage_snapshot(&mut self, file_path: &str, snapshot: Option<IndexEntry>) {
         if !self.tracked_files.contains(&file_path.to_string()){
             self.tracked_files.push(file_path.to_string());
         }
+        self.snapshots.insert(file_path.to_string(), snapshot);
+    }
+
+    // staged_file: the staged content of a path, None for a staged deletion
+    pub fn staged_file(&self, path: &str, file_path: &str) -> Result<Option<FileEntry>, String> {
+        match self.snapshots.get(file_path) {
+            Some(snapshot) => Ok(snapshot.as_ref().map(IndexEntry::file_entry)),
+            None if Path::new(file_path).is_file() => Ok(Some(IndexEntry::from_file(path, file_path)?.file_entry())),
+            None => Ok(None),
+        }
+    }
+
+    // changed_since_staged: whether the file on disk differs from its snapshot
+    pub fn changed_since_staged(&self, file_path: &str) -> bool {
+        match self.snapshots.get(file_path) {
+            Some(Some(entry)) => !entry.is_unchanged(file_path),
+            Some(None) => Path::new(file_path).exists(),
+            None => false,
+        }
+    }
+
+    // is_cached_clean: whether the stat cache vouches that a tracked file still has the content `blob`
+    pub fn is_cached_clean(&self, file_path: &str, blob: &str) -> bool {
+        self.stat_cache.get(file_path).is_some_and(|entry| entry.blob == blob && entry.stat_matches(file_path))
     }
 
     pub fn get_tracked_files(&self) -> &Vec<String> {
@@ -34,14 +291,101 @@ impl StagingArea{
 
     pub fn clear_staging(&mut self){
         self.tracked_files.clear();
+        self.snapshots.clear();
     }
 }
 
-use std::fs;
-use std::io::{Error, Write, Read};
-pub fn writeFile(path: &str, content: &str) -> Result<(), Error> {
-    // Write the provided content to the file at the specified path
-    let mut file = fs::File::create(path)?;
-    file.write_all(content.as_bytes())?;
-    Ok(())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(blob_byte: &str, size: u64) -> IndexEntry {
+        IndexEntry { mode: 0o100644, blob: blob_byte.repeat(32), size, mtime: 1_700_000_000_123_456_789, ctime: -5 }
+    }
+
+    const ROOT: &str = "/work/repo";
+
+    fn at(root: &str, relative: &str) -> String {
+        format!("{}/{}", root, relative)
+    }
+
+    fn sample() -> StagingArea {
+        let mut staging_area = StagingArea::new();
+        staging_area.tracked_files = ["b/new.txt", "gone.txt", "legacy.txt", "ü/日本.bin"].iter().map(|relative| at(ROOT, relative)).collect();
+        staging_area.snapshots.insert(at(ROOT, "b/new.txt"), Some(entry("ab", 12)));
+        staging_area.snapshots.insert(at(ROOT, "gone.txt"), None);
+        staging_area.snapshots.insert(at(ROOT, "ü/日本.bin"), Some(entry("01", u64::MAX)));
+        staging_area.stat_cache.insert(at(ROOT, "clean.txt"), entry("ff", 3));
+        staging_area
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let staging_area = sample();
+        let read = StagingArea::from_index(&staging_area.to_index(ROOT), ROOT).unwrap();
+        assert_eq!(read.tracked_files, staging_area.tracked_files);
+        assert_eq!(read.snapshots, staging_area.snapshots);
+        assert_eq!(read.stat_cache, staging_area.stat_cache);
+        // a path staged without a snapshot stays without one
+        assert!(!read.snapshots.contains_key(&at(ROOT, "legacy.txt")));
+        // writing it again gives the same bytes
+        assert_eq!(read.to_index(ROOT), staging_area.to_index(ROOT));
+    }
+
+    #[test]
+    fn test_index_moved_repository() {
+        // the index holds paths from the root, a moved repository reads them under its new root
+        let data = sample().to_index(ROOT);
+        assert!(!data.windows(ROOT.len()).any(|window| window == ROOT.as_bytes()));
+        let read = StagingArea::from_index(&data, "/elsewhere/renamed").unwrap();
+        assert_eq!(read.tracked_files[0], "/elsewhere/renamed/b/new.txt");
+        assert_eq!(read.snapshots[&at("/elsewhere/renamed", "ü/日本.bin")], Some(entry("01", u64::MAX)));
+        assert_eq!(read.stat_cache[&at("/elsewhere/renamed", "clean.txt")], entry("ff", 3));
+        // a path outside the root (an index written with absolute paths) is kept as it is
+        let mut staging_area = StagingArea::new();
+        staging_area.stage_snapshot("/old/place/a.txt", None);
+        let read = StagingArea::from_index(&staging_area.to_index(ROOT), ROOT).unwrap();
+        assert_eq!(read.tracked_files, vec!["/old/place/a.txt"]);
+    }
+
+    #[test]
+    fn test_index_empty() {
+        let data = StagingArea::new().to_index(ROOT);
+        assert_eq!(data.len(), 12 + 32);
+        let read = StagingArea::from_index(&data, ROOT).unwrap();
+        assert!(read.staging_is_empty());
+        assert!(read.stat_cache.is_empty());
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let data = sample().to_index(ROOT);
+        // one flipped bit anywhere is caught by the checksum
+        for position in [0, 5, 20, data.len() / 2, data.len() - 1] {
+            let mut corrupt = data.clone();
+            corrupt[position] ^= 1;
+            assert!(StagingArea::from_index(&corrupt, ROOT).is_err(), "flipped byte {}", position);
+        }
+        assert_eq!(StagingArea::from_index(&data[..data.len() - 1], ROOT).err(), Some("Index file is corrupt, its checksum does not match".to_string()));
+        assert_eq!(StagingArea::from_index(b"MDVI", ROOT).err(), Some("Not an index file".to_string()));
+        assert_eq!(StagingArea::from_index(b"[]", ROOT).err(), Some("Not an index file".to_string()));
+
+        // a valid checksum over an unknown version or a truncated entry
+        let with_checksum = |content: &[u8]| [content, Sha256::digest(content).as_slice()].concat();
+        let mut future = data[..data.len() - 32].to_vec();
+        future[4..8].copy_from_slice(&2u32.to_be_bytes());
+        assert_eq!(StagingArea::from_index(&with_checksum(&future), ROOT).err(), Some("Unsupported index version 2".to_string()));
+        let truncated = &data[..data.len() - 40];
+        assert_eq!(StagingArea::from_index(&with_checksum(truncated), ROOT).err(), Some("Index file is truncated".to_string()));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let read = StagingArea::from_json(br#"["a.txt","b.txt"]"#).unwrap();
+        assert_eq!(read.tracked_files, vec!["a.txt", "b.txt"]);
+        assert!(read.snapshots.is_empty());
+        let read = StagingArea::from_json(br#"{"files":["a.txt"],"snapshots":{"a.txt":{"mode":33188,"blob":"00","size":1,"mtime":2}}}"#).unwrap();
+        assert_eq!(read.snapshots["a.txt"].as_ref().map(|entry| entry.ctime), Some(0));
+        assert!(StagingArea::from_json(b"{").is_err());
+    }
+}
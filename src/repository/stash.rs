@@ -0,0 +1,305 @@
+use crate::repository::layout;
+use crate::repository::merge::{merge_trees, write_merge};
+use crate::repository::objects;
+use crate::repository::revision::{open_head, open_revision, open_staging_area, revision_tree, short_id, switch_tree, Revision};
+use crate::repository::staging::IndexEntry;
+use crate::repository::status::collect_status;
+use crate::repository::tree::{self, FileEntry};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// A stash entry is a revision whose main parent is the head revision at the time of the push and whose
+// tree is the working directory. Its branch parent is a second revision holding the staging area: the
+// head tree with the staged files, which lists the staged paths as its related files.
+// .mdv/refs/stash keeps the ids of the entries, newest first, stash@{0} is the first line.
+
+fn read_stash_list(path: &str) -> Vec<String> {
+    fs::read_to_string(layout::stash_path(path))
+        .unwrap_or_default()
+        .lines()
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| id.trim().to_string())
+        .collect()
+}
+
+fn write_stash_list(path: &str, ids: &[String]) -> Result<(), String> {
+    if ids.is_empty() {
+        let _ = fs::remove_file(layout::stash_path(path));
+        return Ok(());
+    }
+    fs::write(layout::stash_path(path), ids.join("\n") + "\n").map_err(|err| err.to_string())
+}
+
+// the files of a tree with the given paths replaced by their content on disk, or removed when they are gone
+fn with_disk_files(path: &str, mut files: BTreeMap<String, FileEntry>, relatives: &[String]) -> Result<BTreeMap<String, FileEntry>, String> {
+    for relative in relatives {
+        let file_path = format!("{}/{}", path, relative);
+        if Path::new(&file_path).is_file() {
+            let blob = objects::write_blob(path, &file_path)?;
+            files.insert(relative.clone(), FileEntry { mode: tree::file_mode(&file_path), blob });
+        } else {
+            files.remove(relative);
+        }
+    }
+    Ok(files)
+}
+
+// push: save the staged and the changed tracked files, then bring the working directory back to the head revision
+pub fn push(path: &str, message: Option<&str>, author: &str) -> Result<String, String> {
+    let head_file = open_head(&layout::head_path(path));
+    let head_id = head_file.get_revision_id().to_string();
+    if head_id.is_empty() {
+        return Err("Nothing committed yet, there is no revision to stash the changes on".to_string());
+    }
+    let status = collect_status(path)?;
+    if status.staged.is_empty() && status.modified.is_empty() && status.deleted.is_empty() {
+        return Ok("No local changes to save".to_string());
+    }
+
+    let branch = if head_file.get_branch().is_empty() { "(detached)" } else { head_file.get_branch() };
+    let head_rev = open_revision(&layout::revision_path(path, &head_id));
+    let message = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!("WIP on {}: {} {}", branch, short_id(&head_id), head_rev.get_message().lines().next().unwrap_or("")),
+    };
+
+    let head_tree = revision_tree(path, &head_id);
+    let head_files = match &head_tree {
+        Some(tree_id) => tree::flatten(path, tree_id)?,
+        None => BTreeMap::new(),
+    };
+    let mut staging_area = open_staging_area(path)?;
+    let mut index_files = head_files;
+    for relative in &status.staged {
+        match staging_area.staged_file(path, &format!("{}/{}", path, relative))? {
+            Some(file) => { index_files.insert(relative.clone(), file); },
+            None => { index_files.remove(relative); },
+        }
+    }
+    let index_rev = Revision::new(
+        path.to_string(),
+        Some(head_id.clone()),
+        None,
+        author.to_string(),
+        format!("index on {}", message),
+        status.staged.clone(),
+        tree::build_tree(path, &index_files)?,
+    );
+    index_rev.save_revision();
+
+    // staged files may have changed again since they were added, the work tree takes them from disk as well
+    let changed: Vec<String> = status.modified.iter().chain(&status.deleted).chain(&status.staged).cloned().collect();
+    let work_tree = tree::build_tree(path, &with_disk_files(path, index_files, &changed)?)?;
+    let related_files = tree::diff_trees(path, head_tree.as_deref(), Some(&work_tree))?
+        .into_iter()
+        .map(|(relative, _, _)| relative)
+        .collect();
+    let work_rev = Revision::new(
+        path.to_string(),
+        Some(head_id),
+        Some(index_rev.get_id()),
+        author.to_string(),
+        message.clone(),
+        related_files,
+        work_tree.clone(),
+    );
+    work_rev.save_revision();
+
+    let mut ids = read_stash_list(path);
+    ids.insert(0, work_rev.get_id());
+    write_stash_list(path, &ids)?;
+
+    switch_tree(path, Some(&work_tree), head_tree.as_deref())?;
+    staging_area.clear_staging();
+    staging_area.save(path);
+
+    Ok(format!("Saved working directory and staging area as stash@{{0}}: {}", message))
+}
+
+// list: the stash entries, newest first
+pub fn list(path: &str) -> Result<String, String> {
+    let ids = read_stash_list(path);
+    if ids.is_empty() {
+        return Ok("No stash entries".to_string());
+    }
+    Ok(ids.iter().enumerate()
+        .map(|(index, id)| format!("stash@{{{}}}: {}", index, open_revision(&layout::revision_path(path, id)).get_message()))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+// apply: bring back the files and the staging area of an entry, merged with the head revision when it moved
+// since the push. The entry is dropped afterwards when `drop` is set (pop), unless there were conflicts.
+pub fn apply(path: &str, index: usize, drop: bool) -> Result<String, String> {
+    let mut ids = read_stash_list(path);
+    let id = ids.get(index).cloned().ok_or(format!("No stash entry stash@{{{}}}", index))?;
+    let stash_rev = open_revision(&layout::revision_path(path, &id));
+    stash_rev.verify()?;
+    let (base, index_rev) = match stash_rev.get_parent_ids() {
+        (Some(base), Some(index_rev)) => (base.clone(), index_rev.clone()),
+        _ => return Err(format!("stash@{{{}}} is not a stash entry", index)),
+    };
+
+    let status = collect_status(path)?;
+    if !status.staged.is_empty() || !status.modified.is_empty() || !status.deleted.is_empty() {
+        return Err("Your local changes would be overwritten by the stash, commit or stash them first".to_string());
+    }
+
+    let head_id = open_head(&layout::head_path(path)).get_revision_id().to_string();
+    let head_tree = revision_tree(path, &head_id);
+    let stash_tree = revision_tree(path, &id);
+    let conflicts: Vec<String> = if head_id == base {
+        switch_tree(path, head_tree.as_deref(), stash_tree.as_deref())?;
+        Vec::new()
+    } else {
+        let merged = merge_trees(path, revision_tree(path, &base).as_deref(), head_tree.as_deref(), stash_tree.as_deref(), "Updated upstream", "Stashed changes")?;
+        write_merge(path, head_tree.as_deref(), &merged)?;
+        merged.conflicts.into_keys().collect()
+    };
+
+    // the staged snapshots come back as they were, unless the head moved and the files were merged
+    let index_files = match revision_tree(path, &index_rev) {
+        Some(tree_id) if head_id == base => tree::flatten(path, &tree_id)?,
+        _ => BTreeMap::new(),
+    };
+    let mut staging_area = open_staging_area(path)?;
+    for relative in open_revision(&layout::revision_path(path, &index_rev)).get_related_files() {
+        let file_path = format!("{}/{}", path, relative);
+        if head_id == base {
+            staging_area.stage_snapshot(&file_path, index_files.get(relative).map(|file| IndexEntry::from_object(path, file)));
+        } else if !conflicts.contains(relative) {
+            staging_area.stage_file(path, &file_path)?;
+        }
+    }
+    staging_area.save(path);
+
+    if !conflicts.is_empty() {
+        return Err(format!("Applied stash@{{{}}} with conflicts in: {}\nThe stash entry is kept, fix the conflicts and add the files.", index, conflicts.join(", ")));
+    }
+    if drop {
+        ids.remove(index);
+        write_stash_list(path, &ids)?;
+        return Ok(format!("Applied and dropped stash@{{{}}} ({})", index, short_id(&id)));
+    }
+    Ok(format!("Applied stash@{{{}}}", index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::revision::{add, commit};
+
+    fn repository(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mdv-stash-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        layout::init_repository(&path, false, false, false).unwrap();
+        path
+    }
+
+    fn write(path: &str, name: &str, content: &str) {
+        fs::write(format!("{}/{}", path, name), content).unwrap();
+    }
+
+    fn read(path: &str, name: &str) -> String {
+        fs::read_to_string(format!("{}/{}", path, name)).unwrap()
+    }
+
+    fn commit_files(path: &str, files: &[(&str, &str)]) {
+        for (name, content) in files {
+            write(path, name, content);
+        }
+        add(path, files.iter().map(|(name, _)| *name).collect()).unwrap();
+        commit(path, vec![], "change", "tester", false).unwrap();
+    }
+
+    fn staged_blob(path: &str, name: &str) -> Option<String> {
+        let staging_area = open_staging_area(path).unwrap();
+        staging_area.snapshots.get(&format!("{}/{}", path, name)).cloned().flatten().map(|entry| entry.blob)
+    }
+
+    #[test]
+    fn test_push_and_pop() {
+        let path = repository("pop");
+        assert!(push(&path, None, "tester").is_err());
+        commit_files(&path, &[("a.txt", "1"), ("b.txt", "1"), ("c.txt", "1")]);
+        assert_eq!(push(&path, None, "tester"), Ok("No local changes to save".to_string()));
+
+        // a.txt staged as 2 and changed again to 3, b.txt changed without staging, c.txt deleted
+        write(&path, "a.txt", "2");
+        add(&path, vec!["a.txt"]).unwrap();
+        write(&path, "a.txt", "3");
+        write(&path, "b.txt", "2");
+        fs::remove_file(format!("{}/c.txt", path)).unwrap();
+        let branch = open_head(&layout::head_path(&path)).get_branch().to_string();
+        assert_eq!(push(&path, Some("work"), "tester"), Ok(format!("Saved working directory and staging area as stash@{{0}}: On {}: work", branch)));
+        assert_eq!((read(&path, "a.txt"), read(&path, "b.txt"), read(&path, "c.txt")), ("1".to_string(), "1".to_string(), "1".to_string()));
+        assert!(open_staging_area(&path).unwrap().staging_is_empty());
+
+        assert!(apply(&path, 0, true).unwrap().starts_with("Applied and dropped stash@{0}"));
+        assert_eq!((read(&path, "a.txt"), read(&path, "b.txt")), ("3".to_string(), "2".to_string()));
+        assert!(!Path::new(&format!("{}/c.txt", path)).exists());
+        // the staged snapshot comes back as it was added, not as it is on disk
+        assert_eq!(staged_blob(&path, "a.txt"), Some(objects::hash_object(b"2")));
+        assert_eq!(open_staging_area(&path).unwrap().tracked_files, vec![format!("{}/a.txt", path)]);
+        assert_eq!(list(&path), Ok("No stash entries".to_string()));
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_pop_after_head_moved() {
+        let path = repository("moved");
+        commit_files(&path, &[("a.txt", "1\n2\n3\n"), ("b.txt", "1")]);
+        write(&path, "a.txt", "one\n2\n3\n");
+        write(&path, "b.txt", "2");
+        add(&path, vec!["b.txt"]).unwrap();
+        push(&path, None, "tester").unwrap();
+        commit_files(&path, &[("a.txt", "1\n2\nthree\n")]);
+
+        assert!(apply(&path, 0, true).is_ok());
+        assert_eq!(read(&path, "a.txt"), "one\n2\nthree\n");
+        assert_eq!(read(&path, "b.txt"), "2");
+        // the merged files are staged again from disk
+        assert_eq!(staged_blob(&path, "b.txt"), Some(objects::hash_object(b"2")));
+        assert_eq!(list(&path), Ok("No stash entries".to_string()));
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_pop_conflict_keeps_entry() {
+        let path = repository("conflict");
+        commit_files(&path, &[("a.txt", "1\n2\n3\n")]);
+        write(&path, "a.txt", "1\nstashed\n3\n");
+        push(&path, None, "tester").unwrap();
+        commit_files(&path, &[("a.txt", "1\ncommitted\n3\n")]);
+
+        let result = apply(&path, 0, true);
+        assert!(result.unwrap_err().starts_with("Applied stash@{0} with conflicts in: a.txt"));
+        assert_eq!(read(&path, "a.txt"), "1\n<<<<<<< Updated upstream\ncommitted\n=======\nstashed\n>>>>>>> Stashed changes\n3\n");
+        assert_eq!(read_stash_list(&path).len(), 1);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stash_indexes() {
+        let path = repository("indexes");
+        commit_files(&path, &[("a.txt", "1"), ("b.txt", "1")]);
+        write(&path, "a.txt", "first");
+        push(&path, Some("first"), "tester").unwrap();
+        write(&path, "b.txt", "second");
+        push(&path, Some("second"), "tester").unwrap();
+        let branch = open_head(&layout::head_path(&path)).get_branch().to_string();
+        assert_eq!(list(&path), Ok(format!("stash@{{0}}: On {0}: second\nstash@{{1}}: On {0}: first", branch)));
+
+        // apply keeps the entry, pop of an older entry leaves the newer ones in place
+        assert_eq!(apply(&path, 1, false), Ok("Applied stash@{1}".to_string()));
+        assert_eq!((read(&path, "a.txt"), read(&path, "b.txt")), ("first".to_string(), "1".to_string()));
+        assert!(apply(&path, 0, true).unwrap_err().starts_with("Your local changes would be overwritten"));
+        write(&path, "a.txt", "1");
+        assert!(apply(&path, 1, true).is_ok());
+        assert_eq!(list(&path), Ok(format!("stash@{{0}}: On {}: second", branch)));
+        assert_eq!(apply(&path, 5, true), Err("No stash entry stash@{5}".to_string()));
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
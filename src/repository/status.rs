@@ -0,0 +1,111 @@
+use crate::repository::ignore;
+use crate::repository::layout;
+use crate::repository::objects;
+use crate::repository::revision::{open_head, open_staging_area, relative_path, revision_tree};
+use crate::repository::staging::IndexEntry;
+use crate::repository::tree;
+use std::collections::{BTreeMap, HashSet};
+
+// Status compares the working directory, the staging area and the tree of the head revision
+#[derive(Debug, Default)]
+pub struct Status {
+    pub branch: String,
+    pub staged: Vec<String>,    // staged for the next commit
+    pub changed_after_add: Vec<String>, // staged, then changed again on disk
+    pub modified: Vec<String>,  // tracked, changed on disk but not staged
+    pub deleted: Vec<String>,   // tracked, missing on disk
+    pub untracked: Vec<String>, // on disk, neither tracked nor staged
+}
+
+impl Status {
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty() && self.modified.is_empty() && self.deleted.is_empty() && self.untracked.is_empty()
+    }
+}
+
+// collect_status: paths in every group are relative to the repository root
+pub fn collect_status(path: &str) -> Result<Status, String> {
+    let head_file = open_head(&layout::head_path(path));
+    let mut staging_area = open_staging_area(path)?;
+    let tracked = match revision_tree(path, head_file.get_revision_id()) {
+        Some(tree_id) => tree::flatten(path, &tree_id)?,
+        None => BTreeMap::new(),
+    };
+
+    let staged: Vec<String> = staging_area.get_tracked_files().iter()
+        .map(|file_path| relative_path(path, file_path))
+        .collect();
+    let working_files = ignore::working_files(path);
+
+    let staged_set: HashSet<&String> = staged.iter().collect();
+
+    let mut cache_changed = false;
+    let mut status = Status { branch: head_file.get_branch().to_string(), staged: staged.clone(), ..Status::default() };
+    for (relative, file) in &tracked {
+        if staged_set.contains(relative) {
+            continue;
+        }
+        // files with unchanged stat data are not hashed again, clean files that were hashed are cached
+        let file_path = format!("{}/{}", path, relative);
+        if staging_area.is_cached_clean(&file_path, &file.blob) {
+            continue;
+        }
+        match objects::hash_file(&file_path) {
+            Some(blob) if blob != file.blob => status.modified.push(relative.clone()),
+            Some(_) => {
+                if let Some(entry) = IndexEntry::with_stat(&file_path, &file.blob) {
+                    staging_area.stat_cache.insert(file_path, entry);
+                    cache_changed = true;
+                }
+            },
+            None => status.deleted.push(relative.clone()),
+        }
+    }
+    // entries of files that are no longer tracked are dropped
+    let cached = staging_area.stat_cache.len();
+    staging_area.stat_cache.retain(|file_path, _| tracked.contains_key(&relative_path(path, file_path)));
+    if cache_changed || staging_area.stat_cache.len() != cached {
+        staging_area.save(path);
+    }
+    status.untracked = working_files.into_iter()
+        .filter(|relative| !tracked.contains_key(relative) && !staged_set.contains(relative))
+        .collect();
+    status.staged.sort();
+    status.changed_after_add = status.staged.iter()
+        .filter(|relative| staging_area.changed_since_staged(&format!("{}/{}", path, relative)))
+        .cloned()
+        .collect();
+
+    Ok(status)
+}
+
+// status: show the staged, modified, deleted and untracked files
+pub fn status(path: &str) -> Result<String, String> {
+    let status = collect_status(path)?;
+    let mut lines = Vec::new();
+    if status.branch.is_empty() {
+        lines.push("HEAD detached".to_string());
+    } else {
+        lines.push(format!("On branch {}", status.branch));
+    }
+    if status.is_clean() {
+        lines.push("Nothing to commit, working tree clean".to_string());
+        return Ok(lines.join("\n"));
+    }
+
+    let groups = [
+        ("Changes to be committed:", "staged", &status.staged),
+        ("Changed since they were staged (add them again to commit the changes):", "modified", &status.changed_after_add),
+        ("Changes not staged for commit:", "modified", &status.modified),
+        ("Deleted files:", "deleted", &status.deleted),
+        ("Untracked files:", "untracked", &status.untracked),
+    ];
+    for (title, label, files) in groups {
+        if files.is_empty() {
+            continue;
+        }
+        lines.push(title.to_string());
+        lines.extend(files.iter().map(|file| format!("\t{}: {}", label, file)));
+    }
+    Ok(lines.join("\n"))
+}
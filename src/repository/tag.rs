@@ -0,0 +1,213 @@
+use crate::machine::file_management::list_files_recursive;
+use crate::repository::layout;
+use crate::repository::objects;
+use crate::repository::refs::{is_valid_ref_name, remove_ref, write_ref};
+use crate::repository::revision::{open_head, open_revision, resolve_revision, short_id};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+// A tag is a file under .mdv/refs/tags/<tag>. A lightweight tag holds a revision id,
+// an annotated tag holds the id of a tag object in the object store, which names the revision.
+
+#[derive(Serialize,Deserialize,Debug,Clone)]
+struct TagInfo {
+    object: String, // SHA-256 of the tagged revision
+    tag: String,
+    tagger: String,
+    date: String,
+    message: String,
+}
+
+// a tag resolved to its revision, with the tag object of an annotated tag
+#[derive(Debug, Clone)]
+pub struct TagTarget {
+    pub revision: String,
+    pub annotation: Option<(String, String, String)>, // tagger, date, message
+}
+
+// read_tag: the revision a tag names, None if there is no such tag
+pub fn read_tag(path: &str, name: &str) -> Option<TagTarget> {
+    if !is_valid_ref_name(name) {
+        return None;
+    }
+    let id = fs::read_to_string(layout::tag_path(path, name)).ok()?.trim().to_string();
+    if id.is_empty() {
+        return None;
+    }
+    if Path::new(&layout::revision_path(path, &id)).is_file() {
+        return Some(TagTarget { revision: id, annotation: None });
+    }
+    let info: TagInfo = serde_json::from_slice(&objects::read_object(path, &id).ok()?).ok()?;
+    Some(TagTarget { revision: info.object, annotation: Some((info.tagger, info.date, info.message)) })
+}
+
+// list_tags: every tag with its revision, sorted by version
+pub fn list_tags(path: &str) -> Vec<(String, TagTarget)> {
+    let tags_dir = layout::tags_dir(path);
+    let mut tags: Vec<(String, TagTarget)> = list_files_recursive(&tags_dir, &[])
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|file_path| {
+            let name = file_path.strip_prefix(&format!("{}/", tags_dir))?.to_string();
+            let target = read_tag(path, &name)?;
+            Some((name, target))
+        })
+        .collect();
+    tags.sort_by(|(a, _), (b, _)| compare_versions(a, b));
+    tags
+}
+
+// version: MAJOR.MINOR.PATCH with an optional `v` prefix and `-prerelease` suffix (build metadata is ignored)
+fn version(name: &str) -> Option<(Vec<u64>, Option<&str>)> {
+    let name = name.strip_prefix('v').unwrap_or(name);
+    let name = name.split('+').next().unwrap_or(name);
+    let (core, pre) = match name.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (name, None),
+    };
+    let numbers: Vec<u64> = core.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    if numbers.len() == 3 { Some((numbers, pre)) } else { None }
+}
+
+// prerelease identifiers compare numerically when both are numbers, numbers sort before words
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_part), Some(b_part)) => {
+                let ordering = match (a_part.parse::<u64>(), b_part.parse::<u64>()) {
+                    (Ok(a_number), Ok(b_number)) => a_number.cmp(&b_number),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => a_part.cmp(b_part),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            },
+        }
+    }
+}
+
+// compare_versions: semantic versions in version order (a release after its prereleases),
+// then every other name alphabetically
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (version(a), version(b)) {
+        (Some((a_numbers, a_pre)), Some((b_numbers, b_pre))) => a_numbers.cmp(&b_numbers)
+            .then_with(|| match (a_pre, b_pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a_pre), Some(b_pre)) => compare_prerelease(a_pre, b_pre),
+            })
+            .then_with(|| a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+// create_tag: a lightweight tag, or an annotated one when there is a message
+pub fn create_tag(path: &str, name: &str, revision: Option<&str>, message: Option<&str>, tagger: &str) -> Result<String, String> {
+    if !is_valid_ref_name(name) {
+        return Err(format!("{} is not a valid tag name", name));
+    }
+    if Path::new(&layout::tag_path(path, name)).exists() {
+        return Err(format!("A tag named {} already exists", name));
+    }
+    let rev_id = match revision {
+        Some(revision) => resolve_revision(path, revision)?.0,
+        None => {
+            let head_file = open_head(&layout::head_path(path));
+            if head_file.get_revision_id().is_empty() {
+                return Err("Nothing committed yet, a tag needs a revision to name".to_string());
+            }
+            head_file.get_revision_id().to_string()
+        },
+    };
+
+    let ref_id = match message {
+        Some(message) => {
+            let info = TagInfo {
+                object: rev_id.clone(),
+                tag: name.to_string(),
+                tagger: tagger.to_string(),
+                date: Local::now().to_rfc3339(),
+                message: message.to_string(),
+            };
+            objects::write_object(path, serde_json::to_string(&info).unwrap().as_bytes())?
+        },
+        None => rev_id.clone(),
+    };
+    write_ref(&layout::tag_path(path, name), &ref_id)?;
+
+    let kind = if message.is_some() { "annotated tag" } else { "tag" };
+    Ok(format!("Created {} {} at {}", kind, name, short_id(&rev_id)))
+}
+
+pub fn delete_tag(path: &str, name: &str) -> Result<String, String> {
+    let target = read_tag(path, name).ok_or(format!("Tag {} not found", name))?;
+    remove_ref(&layout::tag_path(path, name), &layout::tags_dir(path))?;
+    Ok(format!("Deleted tag {} (was {})", name, short_id(&target.revision)))
+}
+
+// tags: one line per tag with its revision and the tag message, or the revision message of a lightweight tag
+pub fn tags(path: &str) -> Result<String, String> {
+    let tags = list_tags(path);
+    if tags.is_empty() {
+        return Ok("No tags yet".to_string());
+    }
+    Ok(tags.iter()
+        .map(|(name, target)| {
+            let message = match &target.annotation {
+                Some((_, _, message)) => message.clone(),
+                None => open_revision(&layout::revision_path(path, &target.revision)).get_message().to_string(),
+            };
+            format!("{} {} {}", name, short_id(&target.revision), message.lines().next().unwrap_or(""))
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        names.sort_by(|a, b| compare_versions(a, b));
+        names
+    }
+
+    #[test]
+    fn test_compare_versions_numeric() {
+        assert_eq!(sorted(&["v1.10.0", "v1.2.0", "v1.9.3", "v0.1.0"]), vec!["v0.1.0", "v1.2.0", "v1.9.3", "v1.10.0"]);
+        // the v prefix and build metadata do not change the order
+        assert_eq!(compare_versions("1.2.3", "v1.2.4"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.3+build.5", "1.2.3+build.1"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_compare_versions_prerelease() {
+        // semver precedence: 1.0.0-alpha < 1.0.0-alpha.1 < 1.0.0-alpha.beta < 1.0.0-beta < 1.0.0-beta.2 < 1.0.0-beta.11 < 1.0.0-rc.1 < 1.0.0
+        let expected = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0"];
+        let mut shuffled = expected.to_vec();
+        shuffled.reverse();
+        shuffled.swap(1, 5);
+        assert_eq!(sorted(&shuffled), expected);
+    }
+
+    #[test]
+    fn test_compare_versions_other_names() {
+        // names that are not versions come after the versions, alphabetically
+        assert_eq!(sorted(&["release", "v2.0.0", "1.2", "beta", "v1.0.0"]), vec!["v1.0.0", "v2.0.0", "1.2", "beta", "release"]);
+        assert_eq!(compare_versions("v1.0.0.0", "v1.0.0"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Serialize, Deserialize};
+use crate::repository::layout;
+use crate::repository::objects;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Tree objects replace the flat `Directory` of the file log: one tree per directory,
+// mapping entry names to blobs or sub-trees, stored in the object store like blobs.
+
+pub const MODE_FILE: u32 = 0o100644;
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+pub const MODE_TREE: u32 = 0o040000;
+
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct TreeEntry{
+    pub mode: u32,
+    pub id: String, // SHA-256 of the blob or sub-tree
+}
+
+impl TreeEntry {
+    pub fn is_tree(&self) -> bool {
+        self.mode == MODE_TREE
+    }
+}
+
+#[derive(Serialize,Deserialize,Debug,Default)]
+pub struct Tree{
+    pub entries: BTreeMap<String, TreeEntry>, // entry name -> blob or sub-tree
+}
+
+// one file of a flattened tree
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct FileEntry{
+    pub mode: u32,
+    pub blob: String,
+}
+
+impl Tree {
+    pub fn read(path: &str, id: &str) -> Result<Tree, String> {
+        let content = objects::read_object(path, id)?;
+        serde_json::from_slice(&content).map_err(|_| format!("Object {} is not a tree", id))
+    }
+
+    // write: store the tree and return its id, the JSON of a BTreeMap is canonical so equal trees get equal ids
+    pub fn write(&self, path: &str) -> Result<String, String> {
+        let data = serde_json::to_string(&self).unwrap();
+        objects::write_object(path, data.as_bytes())
+    }
+}
+
+// build_tree: write the trees for a set of files (relative path -> file) and return the root tree id
+pub fn build_tree(path: &str, files: &BTreeMap<String, FileEntry>) -> Result<String, String> {
+    for relative in files.keys() {
+        check_path(relative).map_err(|err| format!("{}: {}", relative, err))?;
+    }
+    build_sub_tree(path, files)
+}
+
+fn build_sub_tree(path: &str, files: &BTreeMap<String, FileEntry>) -> Result<String, String> {
+    let mut tree = Tree::default();
+    let mut sub_dirs: BTreeMap<String, BTreeMap<String, FileEntry>> = BTreeMap::new();
+
+    for (relative, file) in files {
+        match relative.split_once('/') {
+            Some((dir, rest)) => {
+                sub_dirs.entry(dir.to_string()).or_default().insert(rest.to_string(), file.clone());
+            },
+            None => {
+                tree.entries.insert(relative.clone(), TreeEntry { mode: file.mode, id: file.blob.clone() });
+            },
+        }
+    }
+    for (dir, dir_files) in sub_dirs {
+        let id = build_sub_tree(path, &dir_files)?;
+        tree.entries.insert(dir, TreeEntry { mode: MODE_TREE, id });
+    }
+
+    tree.write(path)
+}
+
+// flatten: every file recorded under a tree, keyed by its path relative to the repository root
+pub fn flatten(path: &str, tree_id: &str) -> Result<BTreeMap<String, FileEntry>, String> {
+    let mut files = BTreeMap::new();
+    flatten_into(path, tree_id, "", &mut files)?;
+    Ok(files)
+}
+
+fn flatten_into(path: &str, tree_id: &str, prefix: &str, files: &mut BTreeMap<String, FileEntry>) -> Result<(), String> {
+    for (name, entry) in Tree::read(path, tree_id)?.entries {
+        let relative = join_path(prefix, &name);
+        if entry.is_tree() {
+            flatten_into(path, &entry.id, &relative, files)?;
+        } else {
+            files.insert(relative, FileEntry { mode: entry.mode, blob: entry.id });
+        }
+    }
+    Ok(())
+}
+
+// lookup: find a single file of a tree without flattening the whole tree
+pub fn lookup(path: &str, tree_id: &str, relative: &str) -> Result<Option<FileEntry>, String> {
+    let mut current = tree_id.to_string();
+    let mut parts = relative.split('/').peekable();
+    while let Some(name) = parts.next() {
+        let tree = Tree::read(path, &current)?;
+        match tree.entries.get(name) {
+            Some(entry) if parts.peek().is_none() && !entry.is_tree() => {
+                return Ok(Some(FileEntry { mode: entry.mode, blob: entry.id.clone() }));
+            },
+            Some(entry) if parts.peek().is_some() && entry.is_tree() => current = entry.id.clone(),
+            _ => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+// (path, old file, new file) of a file that differs between two trees
+pub type TreeChange = (String, Option<FileEntry>, Option<FileEntry>);
+
+// diff_trees: files that differ between two trees as (path, old, new), identical sub-trees are skipped by id
+pub fn diff_trees(path: &str, old: Option<&str>, new: Option<&str>) -> Result<Vec<TreeChange>, String> {
+    let mut changes = Vec::new();
+    diff_into(path, old, new, "", &mut changes)?;
+    Ok(changes)
+}
+
+fn diff_into(path: &str, old: Option<&str>, new: Option<&str>, prefix: &str, changes: &mut Vec<TreeChange>) -> Result<(), String> {
+    if old == new {
+        return Ok(());
+    }
+    let old_tree = match old { Some(id) => Tree::read(path, id)?, None => Tree::default() };
+    let new_tree = match new { Some(id) => Tree::read(path, id)?, None => Tree::default() };
+
+    let mut names: Vec<&String> = old_tree.entries.keys().chain(new_tree.entries.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let relative = join_path(prefix, name);
+        let old_entry = old_tree.entries.get(name);
+        let new_entry = new_tree.entries.get(name);
+        if old_entry == new_entry {
+            continue;
+        }
+        let old_sub = old_entry.filter(|entry| entry.is_tree()).map(|entry| entry.id.as_str());
+        let new_sub = new_entry.filter(|entry| entry.is_tree()).map(|entry| entry.id.as_str());
+        if old_sub.is_some() || new_sub.is_some() {
+            diff_into(path, old_sub, new_sub, &relative, changes)?;
+        }
+        let old_file = old_entry.filter(|entry| !entry.is_tree()).map(|entry| FileEntry { mode: entry.mode, blob: entry.id.clone() });
+        let new_file = new_entry.filter(|entry| !entry.is_tree()).map(|entry| FileEntry { mode: entry.mode, blob: entry.id.clone() });
+        if old_file != new_file {
+            changes.push((relative, old_file, new_file));
+        }
+    }
+    Ok(())
+}
+
+// check_path: a path recorded in a tree must stay inside the working tree, every part of it is a plain
+// name and it does not lead into the repository folder
+pub fn check_path(relative: &str) -> Result<(), String> {
+    let valid = relative.split('/').all(|name| !name.is_empty() && name != "." && name != ".." && !name.contains('\0'))
+        && relative.split('/').next() != Some(layout::MDV_DIR);
+    if valid { Ok(()) } else { Err("Not a valid path inside the working tree".to_string()) }
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() { name.to_string() } else { format!("{}/{}", prefix, name) }
+}
+
+// file_mode: mode a working file is recorded with
+#[cfg(unix)]
+pub fn file_mode(file_path: &str) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(file_path) {
+        Ok(metadata) if metadata.permissions().mode() & 0o111 != 0 => MODE_EXECUTABLE,
+        _ => MODE_FILE,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_file_path: &str) -> u32 {
+    MODE_FILE
+}
+
+// restore_file: write a file of a tree back into the working directory with its mode
+pub fn restore_file(path: &str, relative: &str, file: &FileEntry) -> Result<(), String> {
+    check_path(relative)?;
+    let file_path = format!("{}/{}", path, relative);
+    objects::restore_blob(path, &file.blob, &file_path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if file.mode == MODE_EXECUTABLE { 0o755 } else { 0o644 };
+        let _ = fs::set_permissions(&file_path, fs::Permissions::from_mode(mode));
+    }
+    Ok(())
+}
+
+// remove_file: delete a working file and the folders it leaves empty
+pub fn remove_file(path: &str, relative: &str) -> Result<(), String> {
+    check_path(relative)?;
+    let file_path = format!("{}/{}", path, relative);
+    fs::remove_file(&file_path).map_err(|err| format!("{}: {}", relative, err))?;
+    let mut parent = Path::new(&file_path).parent();
+    while let Some(dir) = parent.filter(|dir| dir != &Path::new(path)) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        parent = dir.parent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_path() {
+        for relative in ["a.txt", "dir/a.txt", ".mdvignore", "a/.mdv/b", "..a", "a..", "a b/c"] {
+            assert_eq!(check_path(relative), Ok(()), "{}", relative);
+        }
+        for relative in ["", "..", "../a.txt", "a/../../b", "./a", "a/./b", "/a", "a/", "a//b", ".mdv", ".mdv/HEAD", "a\0b"] {
+            assert!(check_path(relative).is_err(), "{}", relative);
+        }
+    }
+
+    #[test]
+    fn test_invalid_paths_are_refused() {
+        // the checks come before anything is read or written
+        let path = "/nonexistent/mdv-tree-test";
+        let file = FileEntry { mode: MODE_FILE, blob: "00".repeat(32) };
+        let files = BTreeMap::from([("a.txt".to_string(), file.clone()), ("../outside.txt".to_string(), file.clone())]);
+        assert_eq!(build_tree(path, &files), Err("../outside.txt: Not a valid path inside the working tree".to_string()));
+        assert_eq!(restore_file(path, "../outside.txt", &file), Err("Not a valid path inside the working tree".to_string()));
+        assert_eq!(restore_file(path, "a/../../outside.txt", &file), Err("Not a valid path inside the working tree".to_string()));
+        assert_eq!(remove_file(path, "../outside.txt"), Err("Not a valid path inside the working tree".to_string()));
+    }
+}
@@ -1,4 +0,0 @@
-#[allow(dead_code)]
-pub fn public_function() {
-    println!("called `my::inaccessible::public_function()`");
-}
\ No newline at end of file
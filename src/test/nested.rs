@@ -1,8 +0,0 @@
-pub fn function() {
-    println!("called `my::nested::function()`");
-}
-
-#[allow(dead_code)]
-fn private_function() {
-    println!("called `my::nested::private_function()`");
-}
\ No newline at end of file
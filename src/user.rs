@@ -0,0 +1,2 @@
+pub mod interaction;
+pub mod user_interaction;
@@ -1,9 +1,11 @@
 // Interaction Module
+#[allow(clippy::module_inception)]
 pub mod interaction {
-    use crate::user_interaction::ExecutableCommand;
+    use crate::user::user_interaction::ExecutableCommand;
     use crate::repository::repository::Repository;
+    use crate::repository::layout;
     use std::env;
-    use std::error::Error;
+    use std::io::{self, Write};
     use std::path::{Path, PathBuf};
     // InteractionManager is responsible for managing interactions with the Repository Module.
     pub struct InteractionManager;
@@ -14,64 +16,162 @@ pub mod interaction {
         // Output: Result<(), SendError> (success or send error)
         // This function will handle the logic of interacting with the Repository Module.
         pub fn send_command_to_repository(command: ExecutableCommand) -> Result<String, SendError> {
-            // Current working directory is assumed to be the repository path
-            let repo_path = env::current_dir().map_err(|e| SendError::RepositoryError(e.to_string()))?.to_str().unwrap().to_owned();
-            let repository = Repository::new(&repo_path);
-            let repo_root = Self::find_repo_root(Path::new(&repo_path))
-                            .map_err(|e| SendError::RepositoryError(e))?;
+            // Current working directory is assumed to be inside the repository
+            let repo_path = env::current_dir().map_err(|e| SendError::RepositoryError(e.to_string()))?;
 
+            // init creates the repository in the current directory, there is no root to find yet
+            if let ExecutableCommand::Init(bare, force, wipe) = command {
+                let repository = Repository::new(repo_path.to_str().unwrap());
+                return repository.init(bare, force, wipe).map_err(SendError::RepositoryError);
+            }
+
+            // config also works outside a repository, with the user and system settings only
+            let config_root = Self::find_repo_root(&repo_path).ok().map(|root| root.to_string_lossy().to_string());
+            let config_path = config_root.as_deref();
             match command {
-                ExecutableCommand::Init => {
-                    Repository::init(&repository).map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::ConfigGet(key) => return Repository::config_get(config_path, &key).map_err(SendError::RepositoryError),
+                ExecutableCommand::ConfigSet(layer, key, value) => return Repository::config_set(config_path, layer, &key, &value).map_err(SendError::RepositoryError),
+                ExecutableCommand::ConfigUnset(layer, key) => return Repository::config_unset(config_path, layer, &key).map_err(SendError::RepositoryError),
+                ExecutableCommand::ConfigList => return Repository::config_list(config_path).map_err(SendError::RepositoryError),
+                _ => {},
+            }
+
+            let repo_root = Self::find_repo_root(&repo_path)
+                            .map_err(SendError::RepositoryError)?;
+            let repository = Repository::new(repo_root.to_str().unwrap());
+
+            // path arguments are given from the current folder, the repository takes them from its root
+            let prefix = repo_path.strip_prefix(&repo_root).unwrap_or(Path::new("")).to_path_buf();
+            let to_root = |paths: &[String]| -> Result<Vec<String>, SendError> {
+                paths.iter().map(|path| Self::root_relative(&repo_root, &prefix, path).map_err(SendError::RepositoryError)).collect()
+            };
+            let command = match command {
+                ExecutableCommand::Create(paths) => ExecutableCommand::Create(to_root(&paths)?),
+                ExecutableCommand::Add(paths) => ExecutableCommand::Add(to_root(&paths)?),
+                ExecutableCommand::Remove(paths) => ExecutableCommand::Remove(to_root(&paths)?),
+                ExecutableCommand::CheckIgnore(paths) => ExecutableCommand::CheckIgnore(to_root(&paths)?),
+                ExecutableCommand::Commit(message, author, only, allow_empty) => ExecutableCommand::Commit(message, author, to_root(&only)?, allow_empty),
+                ExecutableCommand::Cat(file_name, revision) => ExecutableCommand::Cat(to_root(&[file_name])?.remove(0), revision),
+                ExecutableCommand::Log(mut filter, graph) => {
+                    filter.path = match filter.path {
+                        Some(path) => Some(to_root(&[path])?.remove(0)),
+                        None => None,
+                    };
+                    ExecutableCommand::Log(filter, graph)
                 },
-                ExecutableCommand::Clone(path) => {
-                    Repository::clone(&repository, &repo_path).map_err(|e| SendError::RepositoryError(e))
+                command => command,
+            };
+
+            // repositories still in the legacy `.rev` format are converted before running anything,
+            // the note goes to stderr so that the output of the command itself stays as it is
+            if repository.needs_upgrade() {
+                let message = repository.upgrade().map_err(SendError::RepositoryError)?;
+                eprintln!("{}", message);
+            }
+
+            match command {
+                ExecutableCommand::Create(file_names) => {
+                    repository.create(file_names.iter().map(|file_name| file_name.as_str()).collect()).map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::Add(paths) => {
+                    repository.add(paths.iter().map(|path| path.as_str()).collect()).map_err(SendError::RepositoryError)
                 },
-                ExecutableCommand::Add(file_name) => {
-                    Repository::add(&repo_path, &file_name).map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::Remove(paths) => {
+                    repository.remove(paths.iter().map(|path| path.as_str()).collect()).map_err(SendError::RepositoryError)
                 },
-                ExecutableCommand::Remove(file_name) => {
-                    Repository::remove(&repo_path, &file_name).map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::Commit(message, author, only, allow_empty) => {
+                    repository.commit(&message, author.as_deref(), only.iter().map(|path| path.as_str()).collect(), allow_empty).map_err(SendError::RepositoryError)
                 },
                 ExecutableCommand::Cat(file_name, revision) => {
-                    Repository::cat(&repo_path, &revision, &file_name).map_err(|e| SendError::RepositoryError(e))
+                    // the content goes to stdout as it is, binary files included; nothing is left to print
+                    let content = repository.cat(&revision, &file_name).map_err(SendError::RepositoryError)?;
+                    let mut stdout = io::stdout().lock();
+                    match stdout.write_all(&content).and_then(|_| stdout.flush()) {
+                        // the reader stopped early (mdv cat f HEAD | head), which is not a failure of cat
+                        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(SendError::RepositoryError(e.to_string())),
+                        _ => Ok(String::new()),
+                    }
                 },
                 ExecutableCommand::Checkout(branch_or_commit) => {
-                    Repository::checkout(&branch_or_commit).map_err(|e| SendError::RepositoryError(e))
+                    repository.checkout(&branch_or_commit).map_err(SendError::RepositoryError)
                 },
-                ExecutableCommand::Commit(message) => {
-                    Repository::commit(&message).map_err(|e| SendError::RepositoryError(e))
-                },
-                ExecutableCommand::Diff(rev1, rev2) => {
-                    Repository::diff(&rev1, &rev2).map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::Diff(rev1, rev2, context) => {
+                    repository.diff(&rev1, &rev2, context).map_err(SendError::RepositoryError)
                 },
                 ExecutableCommand::Merge(source, target) => {
-                    Repository::merge(&source, &target).map_err(|e| SendError::RepositoryError(e))
+                    repository.merge(&source, &target).map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::StashPush(message) => {
+                    repository.stash_push(message.as_deref()).map_err(SendError::RepositoryError)
                 },
-                ExecutableCommand::Pull(remote, branch) => {
-                    Repository::pull(&repository, &branch).map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::StashList => {
+                    repository.stash_list().map_err(SendError::RepositoryError)
                 },
-                ExecutableCommand::Push(remote, branch) => {
-                    Repository::push(&repository, &branch).map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::StashApply(index, drop) => {
+                    repository.stash_apply(index, drop).map_err(SendError::RepositoryError)
                 },
                 ExecutableCommand::Status => {
-                    Repository::status().map_err(|e| SendError::RepositoryError(e))
+                    repository.status().map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::CheckIgnore(paths) => {
+                    repository.check_ignore(paths.iter().map(|path| path.as_str()).collect()).map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::Log(filter, graph) => {
+                    repository.log(&filter, graph).map_err(SendError::RepositoryError)
                 },
                 ExecutableCommand::Heads => {
-                    Repository::heads(&repo_root).map_err(|e| SendError::RepositoryError(e))
+                    repository.heads().map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::Branch(name, start) => {
+                    repository.create_branch(&name, start.as_deref()).map_err(SendError::RepositoryError)
                 },
-                ExecutableCommand::Log => {
-                    Repository::log().map_err(|e| SendError::RepositoryError(e))
+                ExecutableCommand::DeleteBranch(name, force) => {
+                    repository.delete_branch(&name, force).map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::RenameBranch(old_name, new_name) => {
+                    repository.rename_branch(&old_name, &new_name).map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::Tags => {
+                    repository.tags().map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::Tag(name, revision, message) => {
+                    repository.create_tag(&name, revision.as_deref(), message.as_deref()).map_err(SendError::RepositoryError)
+                },
+                ExecutableCommand::DeleteTag(name) => {
+                    repository.delete_tag(&name).map_err(SendError::RepositoryError)
                 },
                 _ => Err(SendError::UnsupportedCommand),
             }
         }
 
+        // root_relative: a path given from the folder `prefix` (relative to the root) as a path from the root.
+        // `.` and `..` are resolved, --all and -A name the whole repository. A path outside the repository is an error.
+        fn root_relative(repo_root: &Path, prefix: &Path, path: &str) -> Result<String, String> {
+            if path == "--all" || path == "-A" {
+                return Ok(".".to_string());
+            }
+            let outside = || format!("{}: the path is outside the repository", path);
+            let (mut parts, relative): (Vec<String>, &str) = match Path::new(path).strip_prefix(repo_root) {
+                Ok(inside) => (Vec::new(), inside.to_str().unwrap_or(path)),
+                Err(_) if Path::new(path).is_absolute() => return Err(outside()),
+                Err(_) => (prefix.iter().map(|part| part.to_string_lossy().to_string()).collect(), path),
+            };
+            for part in relative.split('/') {
+                match part {
+                    "" | "." => {},
+                    ".." => { parts.pop().ok_or_else(outside)?; },
+                    _ => parts.push(part.to_string()),
+                }
+            }
+            Ok(if parts.is_empty() { ".".to_string() } else { parts.join("/") })
+        }
+
         fn find_repo_root(starting_path: &Path) -> Result<PathBuf, String> {
             let mut current_path = starting_path.to_path_buf();
         
             loop {
-                if current_path.join(".mdv").exists() {
+                let current = current_path.to_str().unwrap_or_default();
+                if layout::is_repository(current) || layout::has_legacy_data(current) {
                     return Ok(current_path);
                 }
         
@@ -85,6 +185,7 @@ pub mod interaction {
         // Input: None
         // Output: Result<Response, ReceiveError> (response or receive error)
         // This function will process the responses received from the Repository Module.
+        #[allow(dead_code)] // responses are returned directly, receiving them is not wired up yet
         pub fn receive_response(result: RepositoryResult) -> Result<String, ReceiveError> {
             match result.result {
                 Ok(message) => Ok(message),
@@ -100,15 +201,18 @@ pub mod interaction {
     }
 
     // Error types for receiving responses
+    #[allow(dead_code)]
     pub enum ReceiveError {
         RepositoryError(String),  // Error in receiving response
     }
 
     // Structure representing the response from the repository module
+    #[allow(dead_code)]
     pub struct RepositoryResult {
         result: Result<String, String>,
     }
 
+    #[allow(dead_code)]
     impl RepositoryResult {
         pub fn success(message: String) -> Self {
             RepositoryResult {
@@ -179,4 +283,34 @@ pub mod interaction {
     //         assert!(matches!(InteractionManager::receive_response(), Err(_)));
     //     }
     // }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_root_relative() {
+            let root = Path::new("/work/repo");
+            let at = |prefix: &str, path: &str| InteractionManager::root_relative(root, Path::new(prefix), path);
+            assert_eq!(at("", "a.txt"), Ok("a.txt".to_string()));
+            assert_eq!(at("src", "a.txt"), Ok("src/a.txt".to_string()));
+            assert_eq!(at("src/deep", "../a.txt"), Ok("src/a.txt".to_string()));
+            assert_eq!(at("src", ".."), Ok(".".to_string()));
+            assert_eq!(at("src", "./lib/"), Ok("src/lib".to_string()));
+            assert_eq!(at("src", "--all"), Ok(".".to_string()));
+            assert_eq!(at("src", "/work/repo/docs/b.txt"), Ok("docs/b.txt".to_string()));
+            assert_eq!(at("src", "*.rs"), Ok("src/*.rs".to_string()));
+        }
+
+        #[test]
+        fn test_root_relative_outside() {
+            let root = Path::new("/work/repo");
+            let at = |prefix: &str, path: &str| InteractionManager::root_relative(root, Path::new(prefix), path);
+            assert_eq!(at("", "../outside.txt"), Err("../outside.txt: the path is outside the repository".to_string()));
+            assert!(at("src", "../../outside.txt").is_err());
+            assert!(at("src", "a/../../../outside.txt").is_err());
+            assert!(at("", "/tmp/outside.txt").is_err());
+            assert!(at("", "/work/repository/a.txt").is_err());
+        }
+    }
 }
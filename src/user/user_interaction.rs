@@ -1,8 +1,9 @@
-    use std::path::{Path, PathBuf};
+    use std::path::{Component, Path, PathBuf};
     use regex::Regex;
+    use crate::repository::config::ConfigLayer;
+    use crate::repository::log::LogFilter;
     use std::env;
     use std::fs::OpenOptions;
-    use std::process;
     use std::io::{self, Write};
 
     // CommandInterpreter is responsible for interpreting and processing user commands.
@@ -16,37 +17,57 @@
         // Input: None
         // Output: None (Prints to stdout)
         pub fn guide_user_input() {
-            println!("Currently supporting the following commands (Format: Command - Description):
-                    1. init - Create an empty repository
+            // a closed stdout (mdv help | head) is not an error
+            let _ = writeln!(io::stdout().lock(), "Currently supporting the following commands (Format: Command - Description):
+                    1. init [--bare] [--force | --wipe] - Create an empty repository (--force reinitializes an existing one and keeps its history, --wipe deletes the history, tags and stashes and starts over)
                     2. clone <repo> - Copy an existing repository
-                    3. add <file name> - Add specific files to track
-                    4. remove <file name> - Remove specific files from tracking
+                    3. add <path>... | add . | add --all - Stage files as they are now, folders and globs (*, ?, [...], **) add the new and changed files they match
+                       create <file name>... - Create new empty files
+                    4. remove <path>... - Remove files from tracking, folders and globs remove the staged files they match
                     5. status - Check the current status of the repository
-                    6. heads - Show the current heads
-                    7. diff <revision1> <revision2> - Check changes between revisions
+                       check-ignore <path>... - Show which .mdvignore rule ignores each path
+                    6. heads - List the branches, the current one is marked with *
+                       branch [<name> [<revision>]] - List the branches or create one at the head or a given revision
+                       branch -d|-D <name> - Delete a branch (-D also deletes it when it is not merged)
+                       branch -m <old-name> <new-name> - Rename a branch
+                       tag [-a] [<name> [<revision>] [-m <message>]] - List the tags by version, or tag the head or a given revision (annotated with -m)
+                       tag -d <name> - Delete a tag
+                    7. diff [-U<lines>] <revision1> <revision2> - Check changes between revisions (--staged or --working compare the staging area or the working directory)
                     8. cat <file name> <revision> - Inspect a file of a given revision
                     9. checkout <branch-name or commit-hash> - Check out a specific revision
-                    10. commit -m '<message>' - Commit changes and create a new revision
-                    11. log - View the change log
+                    10. commit -m '<message>' [--author '<name> <email>'] [--only <path>...] [--allow-empty] - Commit the staged files (--only commits just the given paths as they are now)
+                    11. log [-n <count>] [--author <name>] [--since <date>] [--until <date>] [--grep <text>] [--all-parents] [--all] [<path>] - View the change log, newest first (dates as YYYY-MM-DD)
+                        log --graph [-n <count>] [--all] - Draw the history with its branches and merges
                     12. merge <source-branch> <target-branch> - Merge two revisions
+                        stash [push [-m <message>]] - Put the staged and modified files aside and go back to the head revision
+                        stash list | stash pop [<n>] | stash apply [<n>] - List the stash entries, or bring back stash@{{n}} (pop also drops it)
                     13. pull <remote-name> <branch-name> - Pull changes from another repository
                     14. push <remote-name> <branch-name> - Push changes into another repository
+                    15. config get <key> | config list - Show a setting or every setting, with the layer it comes from
+                        config set|unset [--user|--system] <key> [<value>] - Change a setting of the repository (or ~/.mdvconfig, /etc/mdvconfig)
+
+                    A revision can be a branch, a tag, HEAD, a revision id or a unique prefix of at least 4 characters,
+                    followed by ~N (N-th main parent back), ^N (N-th parent) or @{{<date>}} (as of YYYY-MM-DD, yesterday, 2.days.ago), e.g. main~2 or HEAD@{{yesterday}}
                     
+                    Run one command with mdv <command> [<arguments>...], e.g. mdv commit -m \"fix the parser\",
+                    or type commands at the prompt of mdv shell (exit leaves it).
+
                     Example command: add main.txt");
         }
 
 
-        pub fn get_user_input() -> String {
+        // None once the input is closed
+        pub fn get_user_input() -> Option<String> {
             let mut input = String::new();
     
             print!("Enter command: ");
             io::stdout().flush().unwrap();
     
-            io::stdin()
+            let read = io::stdin()
                 .read_line(&mut input)
                 .expect("Failed to read line");
     
-            input.trim().to_string()
+            (read > 0).then(|| input.trim().to_string())
         }
         
         // Method 2: Interpret the user's command
@@ -54,31 +75,80 @@
         // Output: Result<ExecutableCommand, InterpretationError>
         // This function will eventually parse and process user input.
         pub fn interpret_command(input: String) -> Result<ExecutableCommand, InterpretationError> {
-            if input.trim() == "exit" {
-                process::exit(0); // 直接退出程序
+            let words = Self::split_words(&input)?;
+            let parts: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+            Self::interpret_arguments(&parts)
+        }
+
+        // Splits a line typed at the prompt into arguments as a shell would: words are separated by
+        // whitespace, and text in single or double quotes stays in one word without its quotes
+        fn split_words(input: &str) -> Result<Vec<String>, InterpretationError> {
+            let mut words = Vec::new();
+            let mut word: Option<String> = None;
+            let mut chars = input.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' | '"' => {
+                        let word = word.get_or_insert_with(String::new);
+                        loop {
+                            match chars.next() {
+                                Some(next) if next == c => break,
+                                Some(next) => word.push(next),
+                                None => return Err(InterpretationError::new(&format!("Missing closing {} quote.", c))),
+                            }
+                        }
+                    },
+                    c if c.is_whitespace() => words.extend(word.take()),
+                    c => word.get_or_insert_with(String::new).push(c),
+                }
             }
+            words.extend(word);
+            Ok(words)
+        }
+
+        // Interpret a command given as separate arguments, as the shell passes them to `mdv`.
+        // Each argument is taken as it is: it may contain spaces and quotes, e.g. the message of
+        // `mdv commit -m "fix the 'parser'"`
+        pub fn interpret_arguments(parts: &[&str]) -> Result<ExecutableCommand, InterpretationError> {
               // 首先验证用户输入
-            Self::validate_user_input(&input)?;
+            Self::validate_arguments(parts)?;
 
             // 解析命令
-            let parts: Vec<&str> = input.trim().split_whitespace().collect();
             let command = match parts[0] {
-                "init" => ExecutableCommand::Init,
+                "init" => ExecutableCommand::Init(parts.contains(&"--bare"), parts.contains(&"--force"), parts.contains(&"--wipe")),
                 "clone" => ExecutableCommand::Clone(parts[1].to_string()),
-                "add" => ExecutableCommand::Add(parts[1].to_string()),
-                "remove" => ExecutableCommand::Remove(parts[1].to_string()),
+                "add" => ExecutableCommand::Add(parts[1..].iter().map(|path| path.to_string()).collect()),
+                "create" => ExecutableCommand::Create(parts[1..].iter().map(|file_name| file_name.to_string()).collect()),
+                "remove" => ExecutableCommand::Remove(parts[1..].iter().map(|path| path.to_string()).collect()),
                 "cat" => ExecutableCommand::Cat(parts[1].to_string(), parts[2].to_string()),
                 "checkout" => ExecutableCommand::Checkout(parts[1].to_string()),                   
-                "commit" => ExecutableCommand::Commit(parts[2..].join(" ")
-                .trim_matches(|c| c == '\'' || c == '"')
-                .to_string()),                 
-                "diff" => ExecutableCommand::Diff(parts[1].to_string(), parts[2].to_string()),
+                "commit" => Self::parse_commit_command(&parts[1..])?,
+                "diff" => {
+                    let (context, sources) = Self::split_diff_options(&parts[1..])?;
+                    ExecutableCommand::Diff(sources[0].to_string(), sources[1].to_string(), context)
+                },
                 "merge" => ExecutableCommand::Merge(parts[1].to_string(), parts[2].to_string()),
                 "pull" => ExecutableCommand::Pull(parts[1].to_string(), parts[2].to_string()),       
                 "push" => ExecutableCommand::Push(parts[1].to_string(), parts[2].to_string()),
                 "status" => ExecutableCommand::Status,
+                "check-ignore" => ExecutableCommand::CheckIgnore(parts[1..].iter().map(|path| path.to_string()).collect()),
                 "heads" => ExecutableCommand::Heads,
-                "log" => ExecutableCommand::Log,
+                "branch" => match parts[1..] {
+                    [] => ExecutableCommand::Heads,
+                    ["-d", name] => ExecutableCommand::DeleteBranch(name.to_string(), false),
+                    ["-D", name] => ExecutableCommand::DeleteBranch(name.to_string(), true),
+                    ["-m", old_name, new_name] => ExecutableCommand::RenameBranch(old_name.to_string(), new_name.to_string()),
+                    [name] => ExecutableCommand::Branch(name.to_string(), None),
+                    [name, start] => ExecutableCommand::Branch(name.to_string(), Some(start.to_string())),
+                    _ => return Err(InterpretationError::new("Invalid branch command format.")),
+                },
+                "tag" => Self::parse_tag_command(&parts[1..])?,
+                "stash" => Self::parse_stash_command(&parts[1..])?,
+                "config" => Self::parse_config_command(&parts[1..])?,
+                "log" => {
+                    let (filter, graph) = Self::parse_log_options(&parts[1..])?;
+                    ExecutableCommand::Log(filter, graph)
+                },
                 _ => return Err(InterpretationError::new("Unsupported command."))
             };
 
@@ -88,25 +158,34 @@
         }
 
         // Method 3: Validate the input format
-        // Input: input - &str (user input)
+        // Input: parts - &[&str] (the command and its arguments)
         // Output: bool (true if format is valid, false otherwise)
         // This function will check if the input format is correct.
         // Validate the user input format
-        pub fn validate_user_input(input: &str) -> Result<(), InterpretationError> {
-            let parts: Vec<&str> = input.trim().split_whitespace().collect();
+        pub fn validate_arguments(parts: &[&str]) -> Result<(), InterpretationError> {
             if parts.is_empty() {
                 return Err(InterpretationError::new("Input is empty. Please enter a command."));
             }
 
             match parts[0] {
-                "init" | "status" | "heads" | "log" => Self::validate_no_arguments(&parts)?,
-                "add" => Self::validate_add_command(&input)?,
-                "clone" | "remove" | "cat" => Self::validate_file_path(&parts)?,
-                "checkout" => Self::validate_checkout_command(&parts)?,
-                "commit" => Self::validate_commit_command(&parts)?,
-                "diff" => Self::validate_diff_command(&parts)?,
-                "merge" => Self::validate_merge_command(&parts)?,
-                "pull" | "push" => Self::validate_pull_push_command(&parts, parts[0])?,
+                "init" => Self::validate_init_command(parts)?,
+                "status" | "heads" => Self::validate_no_arguments(parts)?,
+                "check-ignore" => Self::validate_check_ignore_command(parts)?,
+                "log" => { Self::parse_log_options(&parts[1..])?; },
+                "add" => Self::validate_add_command(parts)?,
+                "create" => Self::validate_create_command(parts)?,
+                "remove" => Self::validate_remove_command(parts)?,
+                "clone" => Self::validate_file_path(parts)?,
+                "cat" => Self::validate_cat_command(parts)?,
+                "checkout" => Self::validate_checkout_command(parts)?,
+                "commit" => { Self::parse_commit_command(&parts[1..])?; },
+                "diff" => Self::validate_diff_command(parts)?,
+                "merge" => Self::validate_merge_command(parts)?,
+                "branch" => Self::validate_branch_command(parts)?,
+                "tag" => { Self::parse_tag_command(&parts[1..])?; },
+                "stash" => { Self::parse_stash_command(&parts[1..])?; },
+                "config" => { Self::parse_config_command(&parts[1..])?; },
+                "pull" | "push" => Self::validate_pull_push_command(parts, parts[0])?,
                 _ => return Err(InterpretationError::new("Invalid command. Please enter a valid command."))
             }
 
@@ -121,51 +200,245 @@
             Ok(())
         }
 
-        // Validates the 'add' command input
-        pub fn validate_add_command(input: &str) -> Result<(), InterpretationError> {
-            // Split the input into parts for analysis
-            let parts: Vec<&str> = input.split_whitespace().collect();
+        // Validates the 'init' command input, only the --bare, --force and --wipe options are accepted
+        pub fn validate_init_command(parts: &[&str]) -> Result<(), InterpretationError> {
+            if parts[1..].iter().any(|option| !["--bare", "--force", "--wipe"].contains(option)) || (parts.contains(&"--force") && parts.contains(&"--wipe")) {
+                return Err(InterpretationError::new("Invalid init command format. Expected: init [--bare] [--force | --wipe]"));
+            }
+            Ok(())
+        }
 
+        // Validates the 'add' command input
+        pub fn validate_add_command(parts: &[&str]) -> Result<(), InterpretationError> {
             // Check if the input format is correct (e.g., "add filename")
-            if parts.len() != 2 || parts[0] != "add" {
-                return Err(InterpretationError::new("Invalid input format. Expected: add <filename>"));
+            if parts.len() < 2 || parts[0] != "add" {
+                return Err(InterpretationError::new("Invalid input format. Expected: add <path>... or add --all"));
             }
 
-            // Resolve the file name to a full path
-            let file_name = parts[1];
-            let full_path = resolve_working_directory(file_name)?;
+            // folders, globs and `.` are expanded by the repository, which also reports paths that do not exist
+            Ok(())
+        }
 
-            // Check if the file already exists
-            if full_path.exists() {
-                return Err(InterpretationError::new(&format!("File {} already exists. Please use a different name.", file_name)));
+        // Validates the 'create' command input, the new files must not exist yet
+        pub fn validate_create_command(parts: &[&str]) -> Result<(), InterpretationError> {
+            if parts.len() < 2 {
+                return Err(InterpretationError::new("Invalid create command format. Expected: create <filename>..."));
             }
+            for file_name in &parts[1..] {
+                let full_path = resolve_working_directory(file_name)?;
 
-            Self::validate_file_name_format(file_name)?;
+                // Check if the file already exists
+                if full_path.exists() {
+                    return Err(InterpretationError::new(&format!("File {} already exists. Please use a different name.", file_name)));
+                }
+
+                Self::validate_file_name_format(file_name)?;
+            }
+            Ok(())
+        }
 
+        // Validates the 'remove' command input, every single file has to exist
+        pub fn validate_remove_command(parts: &[&str]) -> Result<(), InterpretationError> {
+            if parts.len() < 2 {
+                return Err(InterpretationError::new("Invalid remove command format. Expected: remove <path>..."));
+            }
+            for file_name in parts[1..].iter().filter(|path| !Self::is_pathspec(path)) {
+                Self::validate_file_path(&["remove", file_name])?;
+            }
             Ok(())
         }
 
+        // a path naming several files: `.`, --all, a folder or a glob
+        fn is_pathspec(path: &str) -> bool {
+            path == "." || path == "--all" || path == "-A" || path.contains(['*', '?', '['])
+                || resolve_working_directory(path).is_ok_and(|full_path| full_path.is_dir())
+        }
+
+
+        // Validates the 'check-ignore' command input, at least one path is needed
+        pub fn validate_check_ignore_command(parts: &[&str]) -> Result<(), InterpretationError> {
+            if parts.len() < 2 {
+                return Err(InterpretationError::new("Invalid check-ignore command format. Expected: check-ignore <path>..."));
+            }
+            Ok(())
+        }
 
         pub fn validate_diff_command(parts: &[&str]) -> Result<(), InterpretationError> {
-            if parts.len() != 3 {
-                return Err(InterpretationError::new("Invalid diff command format. Expected: diff <revision1> <revision2>"));
+            let (_, sources) = Self::split_diff_options(&parts[1..])?;
+            if sources.len() != 2 {
+                return Err(InterpretationError::new("Invalid diff command format. Expected: diff [-U<lines>] <revision1> <revision2>"));
             }
-            if !Self::is_valid_revision(parts[1]) || !Self::is_valid_revision(parts[2]) {
+            if !Self::is_valid_revision(sources[0]) || !Self::is_valid_revision(sources[1]) {
                 return Err(InterpretationError::new("Invalid revision format."));
             }
             Ok(())
         }
+
+        // Separates the -U<lines> context option of 'diff' from the two sources to compare
+        fn split_diff_options<'a>(args: &[&'a str]) -> Result<(Option<usize>, Vec<&'a str>), InterpretationError> {
+            let mut context = None;
+            let mut sources = Vec::new();
+            for arg in args {
+                match arg.strip_prefix("-U") {
+                    Some(lines) => {
+                        context = Some(lines.parse().map_err(|_| InterpretationError::new("Invalid number of context lines."))?);
+                    },
+                    None => sources.push(*arg),
+                }
+            }
+            Ok((context, sources))
+        }
         
+        // Reads the filters of 'log' and whether to draw a graph, a single argument that is not an option is the path to follow
+        fn parse_log_options(args: &[&str]) -> Result<(LogFilter, bool), InterpretationError> {
+            let mut filter = LogFilter::default();
+            let mut graph = false;
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                let mut value = |option: &str| {
+                    args.next()
+                        .map(|value| value.to_string())
+                        .ok_or(InterpretationError::new(&format!("Missing value for {}.", option)))
+                };
+                match *arg {
+                    "-n" => {
+                        filter.max_count = Some(value("-n")?.parse().map_err(|_| InterpretationError::new("Invalid number of revisions."))?);
+                    },
+                    "--author" => filter.author = Some(value("--author")?),
+                    "--since" => filter.since = Some(value("--since")?),
+                    "--until" => filter.until = Some(value("--until")?),
+                    "--grep" => filter.message = Some(value("--grep")?),
+                    "--all-parents" => filter.all_parents = true,
+                    "--all" => filter.all_branches = true,
+                    "--graph" => graph = true,
+                    "--" => {},
+                    option if option.starts_with('-') => {
+                        return Err(InterpretationError::new(&format!("Unknown log option {}.", option)));
+                    },
+                    path if filter.path.is_none() => filter.path = Some(path.trim_start_matches("./").to_string()),
+                    _ => return Err(InterpretationError::new("Invalid log command format. Only one path can be followed.")),
+                }
+            }
+            let only_count = LogFilter { max_count: filter.max_count, all_branches: filter.all_branches, ..LogFilter::default() };
+            if graph && filter != only_count {
+                return Err(InterpretationError::new("log --graph can only be combined with -n and --all."));
+            }
+            Ok((filter, graph))
+        }
+
         pub fn validate_merge_command(parts: &[&str]) -> Result<(), InterpretationError> {
             if parts.len() != 3 {
                 return Err(InterpretationError::new("Invalid merge command format. Expected: merge <source-branch> <target-branch>"));
             }
-            if !Self::is_valid_branch(parts[1]) || !Self::is_valid_branch(parts[2]) {
+            if !Self::is_valid_revision(parts[1]) || !Self::is_valid_branch(parts[2]) {
                 return Err(InterpretationError::new("Invalid branch name."));
             }
             Ok(())
         }
         
+        // Validates the 'branch' command input: list, create, delete (-d/-D) or rename (-m)
+        pub fn validate_branch_command(parts: &[&str]) -> Result<(), InterpretationError> {
+            let names = match parts[1..] {
+                [] => vec![],
+                ["-d", name] | ["-D", name] => vec![name],
+                ["-m", old_name, new_name] => vec![old_name, new_name],
+                [name] => vec![name],
+                [name, start] if Self::is_valid_revision(start) => vec![name],
+                _ => return Err(InterpretationError::new(
+                    "Invalid branch command format. Expected: branch [<name> [<revision>]], branch -d|-D <name> or branch -m <old-name> <new-name>"
+                )),
+            };
+            if names.iter().any(|name| !Self::is_valid_branch(name) || name.starts_with('-')) {
+                return Err(InterpretationError::new("Invalid branch name."));
+            }
+            Ok(())
+        }
+
+        // Reads the 'tag' command: listing, deletion with -d, or creation, annotated when a -m message is given
+        fn parse_tag_command(args: &[&str]) -> Result<ExecutableCommand, InterpretationError> {
+            let usage = "Invalid tag command format. Expected: tag, tag [-a] <name> [<revision>] [-m <message>] or tag -d <name>";
+            let (args, message) = match args.iter().position(|arg| *arg == "-m") {
+                Some(index) => {
+                    let message = args.get(index + 1).ok_or(InterpretationError::new("Missing value for -m."))?.to_string();
+                    if message.is_empty() {
+                        return Err(InterpretationError::new("Tag message cannot be empty."));
+                    }
+                    ([&args[..index], &args[index + 2..]].concat(), Some(message))
+                },
+                None => (args.to_vec(), None),
+            };
+            let args = args.as_slice();
+            let annotated = args.first() == Some(&"-a");
+            let args = if annotated { &args[1..] } else { args };
+            if annotated && message.is_none() {
+                return Err(InterpretationError::new("An annotated tag needs a message: tag -a <name> -m <message>"));
+            }
+
+            let command = match args {
+                [] if message.is_none() => ExecutableCommand::Tags,
+                ["-d", name] if message.is_none() => ExecutableCommand::DeleteTag(name.to_string()),
+                [name] => ExecutableCommand::Tag(name.to_string(), None, message),
+                [name, revision] if Self::is_valid_revision(revision) => ExecutableCommand::Tag(name.to_string(), Some(revision.to_string()), message),
+                _ => return Err(InterpretationError::new(usage)),
+            };
+            match &command {
+                ExecutableCommand::Tag(name, _, _) | ExecutableCommand::DeleteTag(name) if !Self::is_valid_branch(name) || name.starts_with('-') => {
+                    Err(InterpretationError::new("Invalid tag name."))
+                },
+                _ => Ok(command),
+            }
+        }
+
+        // Reads the 'config' subcommands, set and unset write the repository config unless --user or --system is given
+        fn parse_config_command(args: &[&str]) -> Result<ExecutableCommand, InterpretationError> {
+            let usage = "Invalid config command format. Expected: config get <key>, config set [--user|--system] <key> <value>, config unset [--user|--system] <key> or config list";
+            let (layer, args) = match args.get(1) {
+                Some(&"--user") => (ConfigLayer::User, [&args[..1], &args[2..]].concat()),
+                Some(&"--system") => (ConfigLayer::System, [&args[..1], &args[2..]].concat()),
+                Some(&"--repo") => (ConfigLayer::Repository, [&args[..1], &args[2..]].concat()),
+                _ => (ConfigLayer::Repository, args.to_vec()),
+            };
+            let valid_key = |key: &str| key.contains('.') && !key.starts_with('.') && !key.ends_with('.');
+            match args.as_slice() {
+                ["list"] => Ok(ExecutableCommand::ConfigList),
+                ["get", key] if valid_key(key) => Ok(ExecutableCommand::ConfigGet(key.to_string())),
+                ["unset", key] if valid_key(key) => Ok(ExecutableCommand::ConfigUnset(layer, key.to_string())),
+                ["set", key, value] if valid_key(key) => Ok(ExecutableCommand::ConfigSet(layer, key.to_string(), value.to_string())),
+                _ => Err(InterpretationError::new(usage)),
+            }
+        }
+
+        // Reads the 'stash' subcommands, entries are given as `n` or `stash@{n}` and default to the newest one
+        fn parse_stash_command(args: &[&str]) -> Result<ExecutableCommand, InterpretationError> {
+            let entry = |arg: Option<&&str>| match arg {
+                None => Ok(0),
+                Some(arg) => arg.strip_prefix("stash@{")
+                    .and_then(|rest| rest.strip_suffix('}'))
+                    .unwrap_or(arg)
+                    .parse()
+                    .map_err(|_| InterpretationError::new("Invalid stash entry, expected a number or stash@{n}.")),
+            };
+            match args {
+                [] | ["push"] => Ok(ExecutableCommand::StashPush(None)),
+                ["push", "-m", message] => Ok(ExecutableCommand::StashPush(Some(message.to_string()))),
+                ["list"] => Ok(ExecutableCommand::StashList),
+                ["pop"] | ["pop", _] => Ok(ExecutableCommand::StashApply(entry(args.get(1))?, true)),
+                ["apply"] | ["apply", _] => Ok(ExecutableCommand::StashApply(entry(args.get(1))?, false)),
+                _ => Err(InterpretationError::new("Invalid stash command format. Expected: stash [push [-m <message>]], stash list, stash pop [<n>] or stash apply [<n>]")),
+            }
+        }
+
+        // Validates the 'cat' command input, the file is looked up in the revision and not on disk
+        pub fn validate_cat_command(parts: &[&str]) -> Result<(), InterpretationError> {
+            if parts.len() != 3 {
+                return Err(InterpretationError::new("Invalid cat command format. Expected: cat <file name> <revision>"));
+            }
+            if !Self::is_valid_revision(parts[2]) {
+                return Err(InterpretationError::new("Invalid revision format."));
+            }
+            Ok(())
+        }
+
         pub fn validate_pull_push_command(parts: &[&str], command: &str) -> Result<(), InterpretationError> {
             if parts.len() != 3 {
                 return Err(InterpretationError::new(
@@ -207,35 +480,30 @@
 
 
         pub fn validate_file_name_format(file_name: &str) -> Result<(), InterpretationError> {
-            // 正则表达式：允许字母、数字、下划线、横线和点
-            let re = Regex::new(r"^[a-zA-Z0-9_.-]+$").expect("Invalid regex pattern");
-        
-            // 检查是否匹配正则表达式
-            if !re.is_match(file_name) {
-                return Err(InterpretationError::new("File name contains invalid characters."));
+            // any name works, Unicode and nested paths included; the repository applies its file name policy.
+            // Only names that cannot be a file inside the repository are refused here.
+            if file_name.chars().any(|c| c.is_control()) {
+                return Err(InterpretationError::new("File name contains control characters."));
             }
-        
-            // 检查文件扩展名是否合法
-            let extension = Path::new(file_name).extension().and_then(|s| s.to_str());
-            match extension {
-                Some("txt") | Some("md") | Some("json") | Some("rs") | Some("cpp") => Ok(()),
-                _ => Err(InterpretationError::new("Invalid file extension. Supported extensions: .txt, .md, .json, .rs, .cpp"))
+            let path = Path::new(file_name);
+            if path.is_absolute() || path.components().any(|part| matches!(part, Component::ParentDir)) {
+                return Err(InterpretationError::new("File name has to stay inside the repository, without .. or a leading /."));
+            }
+            if path.components().any(|part| part.as_os_str() == ".mdv") {
+                return Err(InterpretationError::new("Files inside .mdv belong to the repository."));
             }
+            Ok(())
         }
         
 
-        fn is_valid_branch_or_commit(name: &str) -> bool {
-            let re = Regex::new(r"^[a-zA-Z0-9\-_/.]+$").expect("Invalid regex pattern");
-            re.is_match(name)
-        }
-
+        // Revision specifiers are resolved by the repository, only the characters they use are checked here
         fn is_valid_revision(revision: &str) -> bool {
-            let re = Regex::new(r"^[a-zA-Z0-9\-_]+$").expect("Invalid regex pattern");
+            let re = Regex::new(r"^[a-zA-Z0-9\-_/.~^@{}:]+$").expect("Invalid regex pattern");
             re.is_match(revision)
         }
 
         fn is_valid_branch(branch: &str) -> bool {
-            let re = Regex::new(r"^[a-zA-Z0-9\-_/]+$").expect("Invalid regex pattern");
+            let re = Regex::new(r"^[a-zA-Z0-9\-_/.]+$").expect("Invalid regex pattern");
             re.is_match(branch)
         }
         
@@ -250,7 +518,7 @@
             }
 
             let branch_or_commit = parts[1];
-            if !Self::is_valid_branch_or_commit(branch_or_commit) {
+            if !Self::is_valid_revision(branch_or_commit) {
                 return Err(InterpretationError::new("Invalid branch name or commit hash."));
             }
 
@@ -258,25 +526,47 @@
         }
 
 
-        fn validate_commit_command(parts: &[&str]) -> Result<(), InterpretationError> {
-            // 验证命令格式是否正确
-            if parts.len() < 3 || parts[1] != "-m" {
-                return Err(InterpretationError::new("Invalid commit command format. Expected: commit -m '<message>'"));
+        // Reads the 'commit' options, the message and the author are the argument after their option
+        fn parse_commit_command(args: &[&str]) -> Result<ExecutableCommand, InterpretationError> {
+            let usage = "Invalid commit command format. Expected: commit -m '<message>' [--author '<name> <email>'] [--only <path>...] [--allow-empty]";
+            let value = |index: &mut usize| -> Result<String, InterpretationError> {
+                let value = args.get(*index).ok_or(InterpretationError::new(usage))?;
+                *index += 1;
+                Ok(value.to_string())
+            };
+
+            let mut message = None;
+            let mut author = None;
+            let mut only = Vec::new();
+            let mut allow_empty = false;
+            let mut index = 0;
+            while index < args.len() {
+                index += 1;
+                match args[index - 1] {
+                    "-m" => message = Some(value(&mut index)?),
+                    "--author" => author = Some(value(&mut index)?),
+                    "--allow-empty" => allow_empty = true,
+                    "--only" => {
+                        while index < args.len() && !args[index].starts_with('-') {
+                            only.push(args[index].to_string());
+                            index += 1;
+                        }
+                        if only.is_empty() {
+                            return Err(InterpretationError::new("--only needs at least one path."));
+                        }
+                    },
+                    _ => return Err(InterpretationError::new(usage)),
+                }
             }
-        
-            // 提取并处理提交信息
-            // 这里假设提交信息是从第三个元素开始的所有元素组成的
-            let message = parts[2..].join(" ");
-        
-            // 去除可能的单引号或双引号
-            let trimmed_message = message.trim_matches(|c| c == '\'' || c == '"');
-        
-            // 检查提交信息是否为空
-            if trimmed_message.is_empty() {
+
+            let message = message.ok_or(InterpretationError::new(usage))?;
+            if message.is_empty() {
                 return Err(InterpretationError::new("Commit message cannot be empty."));
             }
-        
-            Ok(())
+            if author.as_deref().is_some_and(|author| author.is_empty()) {
+                return Err(InterpretationError::new("Commit author cannot be empty."));
+            }
+            Ok(ExecutableCommand::Commit(message, author, only, allow_empty))
         }
         
 
@@ -294,6 +584,12 @@
                 message: message.to_string(),
             }
         }
+    }
+
+    impl std::fmt::Display for InterpretationError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{}", self.message)
+        }
     }      
     
     // Method 4: Resolve the working directory from the input
@@ -307,22 +603,42 @@
     }
     
     pub enum ExecutableCommand {
-        Init,
+        Init(bool, bool, bool), // bare, force (reinitialize), wipe (start over)
+        #[allow(dead_code)] // clone, pull and push are not supported yet
         Clone(String), 
-        Add(String), 
-        Remove(String),
+        Create(Vec<String>), // new empty files
+        Add(Vec<String>), // paths, folders or globs
+        Remove(Vec<String>),
         Cat(String, String), 
         Checkout(String),
-        Commit(String), 
-        Diff(String, String),
-        Merge(String, String),
+        Commit(String, Option<String>, Vec<String>, bool), // message, author, only these paths (all staged files when empty), allow empty
+        Diff(String, String, Option<usize>), // from, to, context lines (diff.context when not given)
+        Merge(String, String), // source, target
+        #[allow(dead_code)]
         Pull(String, String),
+        #[allow(dead_code)]
         Push(String, String),
         Status,
+        CheckIgnore(Vec<String>), // paths
         Heads,
-        Log,
+        Branch(String, Option<String>), // name, start revision
+        DeleteBranch(String, bool), // name, force
+        RenameBranch(String, String), // old name, new name
+        Tags,
+        Tag(String, Option<String>, Option<String>), // name, revision, message of an annotated tag
+        DeleteTag(String),
+        StashPush(Option<String>), // message
+        StashList,
+        StashApply(usize, bool), // entry, drop it afterwards (pop)
+        Log(LogFilter, bool), // filters, graph
+        ConfigGet(String), // key
+        ConfigSet(ConfigLayer, String, String), // layer, key, value
+        ConfigUnset(ConfigLayer, String), // layer, key
+        ConfigList,
     }
 
+    // the error reporting below is not wired up to the commands yet
+    #[allow(dead_code)]
     #[derive(Debug)]
     pub enum ErrorType {
         Io,
@@ -334,6 +650,7 @@
     // Input: error - ExecutionError
     // Output: None (side effects may include logging or user notification)
     // This function will handle errors that occur during command execution.
+    #[allow(dead_code)]
     pub fn handle_error(error: ExecutionError) {
         match error.error_type {
             ErrorType::Io => {
@@ -358,14 +675,14 @@
         log_error(&error);
     }
     
+    #[allow(dead_code)]
     fn log_error(error: &ExecutionError) {
         let log_file_path = Path::new("error.log");
     
         let mut file = match OpenOptions::new()
             .create(true)
-            .write(true)
             .append(true)
-            .open(&log_file_path) {
+            .open(log_file_path) {
                 Ok(file) => file,
                 Err(e) => {
                     eprintln!("Failed to open log file: {}", e);
@@ -386,12 +703,14 @@
 
 
     // Error type for execution errors.
+    #[allow(dead_code)]
     pub struct ExecutionError {
         error_type: ErrorType,
         code: Option<u32>,
         message: String,
     }
     
+    #[allow(dead_code)]
     impl ExecutionError {
         pub fn new(error_type: ErrorType, code: Option<u32>, message: &str) -> ExecutionError {
             ExecutionError {
@@ -415,6 +734,51 @@
         }
     }
 
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn commit_of(command: Result<ExecutableCommand, InterpretationError>) -> Option<(String, Option<String>, bool)> {
+            match command {
+                Ok(ExecutableCommand::Commit(message, author, _, allow_empty)) => Some((message, author, allow_empty)),
+                _ => None,
+            }
+        }
+
+        #[test]
+        fn test_split_words() {
+            let split = |input: &str| CommandInterpreter::split_words(input).ok();
+            assert_eq!(split("  add  a.txt\tb.txt "), Some(vec!["add".to_string(), "a.txt".to_string(), "b.txt".to_string()]));
+            assert_eq!(split("commit -m \"use 'x'\" --allow-empty"), Some(vec!["commit".to_string(), "-m".to_string(), "use 'x'".to_string(), "--allow-empty".to_string()]));
+            assert_eq!(split("log --author='A B' ''"), Some(vec!["log".to_string(), "--author=A B".to_string(), String::new()]));
+            assert_eq!(split("commit -m 'unclosed"), None);
+        }
 
+        #[test]
+        fn test_arguments_are_taken_as_given() {
+            // the shell already removed the quotes, the ones left belong to the message
+            let commit = |args: &[&str]| commit_of(CommandInterpreter::interpret_arguments(args));
+            assert_eq!(commit(&["commit", "-m", "use 'x'"]), Some(("use 'x'".to_string(), None, false)));
+            assert_eq!(commit(&["commit", "-m", "'quoted' start", "--allow-empty"]), Some(("'quoted' start".to_string(), None, true)));
+            assert_eq!(commit(&["commit", "--author", "A <a@b>", "-m", "--not-an-option"]), Some(("--not-an-option".to_string(), Some("A <a@b>".to_string()), false)));
+            assert_eq!(commit(&["commit", "-m"]), None);
+
+            match CommandInterpreter::interpret_arguments(&["tag", "v1", "-m", "\"release\"", "HEAD~1"]) {
+                Ok(ExecutableCommand::Tag(name, revision, message)) => {
+                    assert_eq!((name.as_str(), revision.as_deref(), message.as_deref()), ("v1", Some("HEAD~1"), Some("\"release\"")));
+                },
+                _ => panic!("not a tag command"),
+            }
+            assert!(matches!(CommandInterpreter::interpret_arguments(&["stash", "push", "-m", "it's wip"]), Ok(ExecutableCommand::StashPush(Some(message))) if message == "it's wip"));
+            assert!(matches!(CommandInterpreter::interpret_arguments(&["config", "set", "user.name", "'Ann' Lee"]), Ok(ExecutableCommand::ConfigSet(_, _, value)) if value == "'Ann' Lee"));
+        }
 
-    
\ No newline at end of file
+        #[test]
+        fn test_prompt_joins_quoted_words() {
+            let commit = |input: &str| commit_of(CommandInterpreter::interpret_command(input.to_string()));
+            assert_eq!(commit("commit -m \"use 'x'\""), Some(("use 'x'".to_string(), None, false)));
+            assert_eq!(commit("commit -m 'two words' --author 'A B' --allow-empty"), Some(("two words".to_string(), Some("A B".to_string()), true)));
+            assert_eq!(commit("commit -m 'unclosed --allow-empty"), None);
+            assert!(matches!(CommandInterpreter::interpret_command("stash push -m 'work in progress'".to_string()), Ok(ExecutableCommand::StashPush(Some(message))) if message == "work in progress"));
+        }
+    }